@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use turing_monitor::fonts::{self, Font};
+use turing_monitor::framebuffer::Framebuffer;
+use turing_monitor::testing::MockScreen;
+use turing_screen::{Coord, Image, Rect, Rgba, Screen};
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 480;
+
+const FONT_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/res/fonts/dejavu/DejaVuSans-Bold.ttf"
+);
+
+fn background() -> Image {
+    let mut img = Image::new(WIDTH, HEIGHT);
+    for (i, px) in img.buffer.iter_mut().enumerate() {
+        *px = Rgba::new((i % 256) as u8, (i / WIDTH % 256) as u8, 0x80, 0xff);
+    }
+    img
+}
+
+fn bench_copy_image(c: &mut Criterion) {
+    let bg = background();
+    let mut fb = Framebuffer::new(WIDTH, HEIGHT);
+    let widget = Rect::new(40, 120, 240, 20);
+
+    c.bench_function("copy_image full screen", |b| {
+        b.iter(|| fb.copy_image(black_box(&bg), &bg.full(), &Coord::new(0, 0)))
+    });
+    c.bench_function("copy_image widget", |b| {
+        b.iter(|| fb.copy_image(black_box(&bg), &widget, &Coord::new(40, 120)))
+    });
+}
+
+fn bench_draw_text(c: &mut Criterion) {
+    let data = fs::read(FONT_PATH).unwrap();
+    let font = Font::from_data(data).unwrap();
+    let mut cache = font.prerender_ascii(22.0);
+    let bg = background();
+    let pos = Coord::new(40, 60);
+    let color = Rgba::new(0xff, 0xff, 0xff, 0xff);
+
+    c.bench_function("draw_text", |b| {
+        b.iter(|| fonts::draw_text(&mut cache, &bg, color, &pos, black_box(" 42%"), false, true))
+    });
+}
+
+fn bench_render_on(c: &mut Criterion) {
+    let bg = background();
+    let mut fb = Framebuffer::new(WIDTH, HEIGHT);
+    fb.copy_image(&bg, &bg.full(), &Coord::new(0, 0));
+    let mut scr: Box<dyn Screen> = Box::new(MockScreen::new(WIDTH, HEIGHT));
+    let widget = Rect::new(40, 120, 240, 20);
+
+    c.bench_function("render_on full screen", |b| {
+        b.iter(|| {
+            fb.render_on(&mut scr, &fb.full(), &Coord::new(0, 0))
+                .unwrap()
+        })
+    });
+    c.bench_function("render_on widget", |b| {
+        b.iter(|| {
+            fb.render_on(&mut scr, &widget, &Coord::new(40, 120))
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_copy_image, bench_draw_text, bench_render_on);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use turing_monitor::themes;
+
+// Parsing must fail cleanly on bad input, and anything that parses must
+// be usable by the rest of the program.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(theme) = themes::load_from_bytes(data) {
+        let (width, height) = theme.screen_size();
+        for cfg in themes::get_meter_list(&theme) {
+            let _ = cfg.layout.check_bounds(width, height);
+        }
+    }
+});
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::Res;
+
+const IIO_DEVICES: &str = "/sys/bus/iio/devices";
+
+// Map raw illuminance readings to brightness levels. Readings between
+// table entries are interpolated.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub table: Vec<(u32, i32)>,
+    pub threshold: i32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            table: vec![(0, 16), (10, 32), (100, 96), (500, 192), (1000, 255)],
+            threshold: 8,
+        }
+    }
+}
+
+// Ambient light sensor.
+pub struct AmbientLight {
+    path: PathBuf,
+    table: Vec<(u32, i32)>,
+    pub threshold: i32,
+}
+
+impl AmbientLight {
+    // Use the first illuminance sensor found, if any.
+    pub fn find(settings: Settings) -> Option<Self> {
+        let mut table = settings.table;
+        if table.is_empty() {
+            return None;
+        }
+        table.sort_by_key(|&(raw, _)| raw);
+
+        let mut paths: Vec<_> = fs::read_dir(IIO_DEVICES)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path().join("in_illuminance_raw"))
+            .filter(|path| path.exists())
+            .collect();
+        paths.sort();
+
+        let path = paths.into_iter().next()?;
+        tracing::info!("using ambient light sensor {}", path.display());
+        Some(Self {
+            path,
+            table,
+            threshold: settings.threshold,
+        })
+    }
+
+    pub fn read(&self) -> Res<u32> {
+        Ok(fs::read_to_string(&self.path)?.trim().parse()?)
+    }
+
+    pub fn level(&self) -> Res<i32> {
+        Ok(lookup(&self.table, self.read()?))
+    }
+}
+
+fn lookup(table: &[(u32, i32)], raw: u32) -> i32 {
+    let (first, last) = (table[0], table[table.len() - 1]);
+    if raw <= first.0 {
+        return first.1;
+    }
+    if raw >= last.0 {
+        return last.1;
+    }
+    for pair in table.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        if raw < x1 {
+            let t = (raw - x0) as f32 / (x1 - x0) as f32;
+            return y0 + ((y1 - y0) as f32 * t) as i32;
+        }
+    }
+    last.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let table = [(10, 20), (20, 40), (40, 30)];
+        let cases = [
+            (0, 20),
+            (9, 20),
+            (10, 20),
+            (15, 30),
+            (20, 40),
+            (30, 35),
+            (35, 33),
+            (40, 30),
+            (41, 30),
+            (u32::MAX, 30),
+        ];
+        for (raw, level) in cases {
+            assert_eq!(lookup(&table, raw), level, "raw {raw}");
+        }
+    }
+
+    #[test]
+    fn test_lookup_default_table() {
+        let table = Settings::default().table;
+        let cases = [
+            (0, 16),
+            (5, 24),
+            (10, 32),
+            (55, 64),
+            (750, 223),
+            (1000, 255),
+            (5000, 255),
+        ];
+        for (raw, level) in cases {
+            assert_eq!(lookup(&table, raw), level, "raw {raw}");
+        }
+    }
+
+    #[test]
+    fn test_lookup_single_entry() {
+        for raw in [0, 100, 1000] {
+            assert_eq!(lookup(&[(100, 50)], raw), 50);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+// Find BAT0 or the first battery in the power supply class.
+fn find_battery() -> Option<PathBuf> {
+    let bat0 = Path::new(POWER_SUPPLY).join("BAT0");
+    if bat0.exists() {
+        return Some(bat0);
+    }
+
+    let mut batteries: Vec<PathBuf> = fs::read_dir(POWER_SUPPLY)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_to_string(path.join("type"))
+                .map(|t| t.trim() == "Battery")
+                .unwrap_or(false)
+        })
+        .collect();
+    batteries.sort();
+    batteries.into_iter().next()
+}
+
+fn read_value(path: &Path) -> Res<f32> {
+    Ok(fs::read_to_string(path)?.trim().parse::<f32>()?)
+}
+
+// Some batteries report charge in µAh, others report energy in µWh.
+fn charge_percent(bat: &Path) -> Res<f32> {
+    let (now, full) = if bat.join("charge_now").exists() {
+        (bat.join("charge_now"), bat.join("charge_full"))
+    } else {
+        (bat.join("energy_now"), bat.join("energy_full"))
+    };
+    let now = read_value(&now)?;
+    let full = read_value(&full)?;
+    if full == 0.0 {
+        return Ok(0.0);
+    }
+    Ok(now * 100.0 / full)
+}
+
+// Battery charge level
+
+#[derive(Debug)]
+pub struct BatChargePercent {
+    pub id: MeterId,
+}
+
+impl BatChargePercent {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for BatChargePercent {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        // Desktops have no battery, just report zero.
+        let bat = match find_battery() {
+            Some(path) => path,
+            None => return Ok(0.0),
+        };
+
+        charge_percent(&bat)
+    }
+
+    fn name(&self) -> &str {
+        "Battery charge"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+}
+
+// Battery charging state
+
+#[derive(Debug)]
+pub struct BatIsCharging {
+    pub id: MeterId,
+}
+
+impl BatIsCharging {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for BatIsCharging {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let bat = match find_battery() {
+            Some(path) => path,
+            None => return Ok(0.0),
+        };
+
+        let status = fs::read_to_string(bat.join("status"))?;
+        if status.contains("Charging") {
+            Ok(1.0)
+        } else {
+            Ok(0.0)
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Battery charging"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn test_charge_percent() {
+        let dir = std::env::temp_dir().join(format!("turing-monitor-bat-{}", process::id()));
+        let charge = dir.join("charge");
+        let energy = dir.join("energy");
+        fs::create_dir_all(&charge).unwrap();
+        fs::create_dir_all(&energy).unwrap();
+
+        fs::write(charge.join("charge_now"), "1500000\n").unwrap();
+        fs::write(charge.join("charge_full"), "3000000\n").unwrap();
+        assert_eq!(charge_percent(&charge).unwrap(), 50.0);
+
+        fs::write(energy.join("energy_now"), "4500000\n").unwrap();
+        fs::write(energy.join("energy_full"), "6000000\n").unwrap();
+        assert_eq!(charge_percent(&energy).unwrap(), 75.0);
+
+        fs::write(energy.join("energy_full"), "0\n").unwrap();
+        assert_eq!(charge_percent(&energy).unwrap(), 0.0);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(charge_percent(&energy).is_err());
+    }
+}
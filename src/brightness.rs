@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Local, Timelike};
+use serde::{de, Deserialize, Deserializer};
+
+pub const DEFAULT_LEVEL: i32 = 5;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+// Brightness setting: a fixed level or follow the time of day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Brightness {
+    Level(i32),
+    Auto,
+}
+
+impl FromStr for Brightness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Brightness::Auto);
+        }
+        match s.parse::<i32>() {
+            Ok(level) if (0..=255).contains(&level) => Ok(Brightness::Level(level)),
+            _ => Err(format!("invalid brightness '{s}', use 0-255 or auto")),
+        }
+    }
+}
+
+impl fmt::Display for Brightness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Brightness::Level(level) => write!(f, "{level}"),
+            Brightness::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+// Accept both `brightness = 20` and `brightness = "auto"` in the
+// configuration file.
+impl<'de> Deserialize<'de> for Brightness {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Level(i32),
+            Name(String),
+        }
+
+        match Value::deserialize(deserializer)? {
+            Value::Level(level) => level.to_string().parse().map_err(de::Error::custom),
+            Value::Name(name) => name.parse().map_err(de::Error::custom),
+        }
+    }
+}
+
+// Map the time of day to a brightness level. The level ramps from
+// day to night starting at dusk, and back to day starting at dawn.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schedule {
+    pub dawn: u32, // hour
+    pub dusk: u32, // hour
+    pub day: i32,
+    pub night: i32,
+    pub ramp_minutes: u32,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            dawn: 7,
+            dusk: 20,
+            day: 255,
+            night: 16,
+            ramp_minutes: 60,
+        }
+    }
+}
+
+impl Schedule {
+    // Brightness at a minute of the day. A ramp longer than the day or
+    // night is shortened to end when the next one starts, and a dawn at
+    // the same hour as dusk keeps the night level.
+    pub fn level(&self, minute_of_day: u32) -> i32 {
+        let (dawn, dusk) = (self.dawn % 24 * 60, self.dusk % 24 * 60);
+        let since = |start: u32| (minute_of_day + MINUTES_PER_DAY - start) % MINUTES_PER_DAY;
+        let day_length = (dusk + MINUTES_PER_DAY - dawn) % MINUTES_PER_DAY;
+        let night_length = (MINUTES_PER_DAY - day_length) % MINUTES_PER_DAY;
+        let ramp = |length: u32| self.ramp_minutes.min(length).max(1);
+        let lerp =
+            |from: i32, to: i32, t: u32, ramp: u32| from + (to - from) * t as i32 / ramp as i32;
+
+        let (since_dawn, since_dusk) = (since(dawn), since(dusk));
+        let (dawn_ramp, dusk_ramp) = (ramp(day_length), ramp(night_length));
+        if since_dawn < dawn_ramp {
+            lerp(self.night, self.day, since_dawn, dawn_ramp)
+        } else if since_dusk < dusk_ramp {
+            lerp(self.day, self.night, since_dusk, dusk_ramp)
+        } else if since_dawn < since_dusk {
+            // dawn is more recent than dusk
+            self.day
+        } else {
+            self.night
+        }
+    }
+
+    pub fn current_level(&self) -> i32 {
+        let now = Local::now();
+        self.level(now.hour() * 60 + now.minute())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(dawn: u32, dusk: u32, ramp_minutes: u32) -> Schedule {
+        Schedule {
+            dawn,
+            dusk,
+            day: 255,
+            night: 15,
+            ramp_minutes,
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> u32 {
+        hour * 60 + minute
+    }
+
+    #[test]
+    fn test_level() {
+        let s = schedule(7, 20, 60);
+        let cases = [
+            (at(0, 0), 15),
+            (at(6, 59), 15),
+            (at(7, 0), 15),
+            (at(7, 30), 135),
+            (at(8, 0), 255),
+            (at(19, 59), 255),
+            (at(20, 0), 255),
+            (at(20, 30), 135),
+            (at(21, 0), 15),
+            (at(23, 59), 15),
+        ];
+        for (minute, level) in cases {
+            assert_eq!(s.level(minute), level, "minute {minute}");
+        }
+    }
+
+    #[test]
+    fn test_level_past_midnight() {
+        let s = schedule(6, 23, 120);
+        assert_eq!(s.level(at(23, 0)), 255);
+        assert_eq!(s.level(at(0, 30)), 75);
+        assert_eq!(s.level(at(1, 0)), 15);
+        assert_eq!(s.level(at(5, 59)), 15);
+    }
+
+    #[test]
+    fn test_level_same_dawn_and_dusk() {
+        let s = schedule(12, 12, 60);
+        for minute in [at(0, 0), at(12, 0), at(12, 30), at(18, 0)] {
+            assert_eq!(s.level(minute), 15, "minute {minute}");
+        }
+    }
+
+    #[test]
+    fn test_level_long_ramp() {
+        // The day is an hour long, so the dawn ramp takes an hour and
+        // dusk starts from the day level.
+        let s = schedule(7, 8, 120);
+        assert_eq!(s.level(at(7, 30)), 135);
+        assert_eq!(s.level(at(8, 0)), 255);
+        assert_eq!(s.level(at(9, 0)), 135);
+        assert_eq!(s.level(at(10, 0)), 15);
+
+        // A ramp longer than the whole day.
+        let s = schedule(7, 20, 5000);
+        assert_eq!(s.level(at(20, 0)), 255);
+        assert_eq!(s.level(at(7, 0)), 15);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("auto".parse(), Ok(Brightness::Auto));
+        assert_eq!("0".parse(), Ok(Brightness::Level(0)));
+        assert_eq!("255".parse(), Ok(Brightness::Level(255)));
+        for s in ["256", "-1", "", "Auto", "bright"] {
+            assert!(s.parse::<Brightness>().is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_deserialize() {
+        #[derive(Deserialize)]
+        struct Config {
+            brightness: Brightness,
+        }
+        let parse = |s: &str| toml::from_str::<Config>(s).map(|c| c.brightness);
+
+        assert_eq!(parse("brightness = 20").unwrap(), Brightness::Level(20));
+        assert_eq!(parse("brightness = \"auto\"").unwrap(), Brightness::Auto);
+        assert!(parse("brightness = 300").is_err());
+        assert!(parse("brightness = \"dim\"").is_err());
+    }
+}
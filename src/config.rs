@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::als;
+use crate::brightness::{Brightness, Schedule};
+use crate::Res;
+
+// Persistent settings used as defaults for command line options.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub brightness: Option<Brightness>,
+    pub brightness_schedule: Option<Schedule>,
+    pub ambient_light: Option<als::Settings>,
+    pub refresh: Option<u64>,
+    pub port: Option<String>,
+    pub theme: Option<String>,
+    pub themes: Option<Vec<String>>,
+}
+
+pub fn load(path: &Path) -> Res<Config> {
+    let data = std::fs::read_to_string(path)?;
+    let config: Config = toml::from_str(&data)?;
+    Ok(config)
+}
+
+// Find the --config option before the command line is fully parsed.
+pub fn path_from_args() -> Option<PathBuf> {
+    find_path(env::args_os().skip(1))
+}
+
+// Accept --config <path>, --config=<path>, -c <path> and -c<path>.
+fn find_path(mut args: impl Iterator<Item = OsString>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == "--config" || arg == "-c" {
+            return args.next().map(PathBuf::from);
+        }
+        let Some(arg) = arg.to_str() else {
+            continue;
+        };
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+        if let Some(path) = arg.strip_prefix("-c") {
+            return Some(PathBuf::from(path.strip_prefix('=').unwrap_or(path)));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(args: &[&str]) -> Option<PathBuf> {
+        find_path(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn test_find_path() {
+        let path = Some(PathBuf::from("file.toml"));
+        assert_eq!(find(&["--config", "file.toml"]), path);
+        assert_eq!(find(&["--config=file.toml"]), path);
+        assert_eq!(find(&["-c", "file.toml"]), path);
+        assert_eq!(find(&["-cfile.toml"]), path);
+        assert_eq!(find(&["-c=file.toml"]), path);
+        assert_eq!(find(&["--dry-run", "-c", "file.toml", "--once"]), path);
+    }
+
+    #[test]
+    fn test_find_path_missing() {
+        assert_eq!(find(&[]), None);
+        assert_eq!(find(&["--dry-run"]), None);
+        assert_eq!(find(&["--config"]), None);
+        assert_eq!(find(&["-c"]), None);
+    }
+}
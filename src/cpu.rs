@@ -1,56 +1,172 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use psutil::{cpu, sensors};
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::fs::File;
+#[cfg(target_os = "linux")]
+use std::io::{BufRead, BufReader, Read};
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
 
-use crate::meter::Meter;
+#[cfg(not(target_os = "linux"))]
+use psutil::cpu;
+use psutil::sensors;
+
+use crate::meter::{Meter, MeterId};
 use crate::Res;
 
 // CPU percentage
 
+#[cfg(target_os = "linux")]
+const PROC_STAT: &str = "/proc/stat";
+
 #[derive(Debug)]
 pub struct CpuPercentage {
-    pub id: u64,
+    pub id: MeterId,
+    #[cfg(target_os = "linux")]
+    last: (u64, u64),
+    #[cfg(not(target_os = "linux"))]
     cpc: cpu::CpuPercentCollector,
 }
 
 impl CpuPercentage {
-    pub fn new(id: u64) -> Res<Self> {
+    #[cfg(target_os = "linux")]
+    pub fn new(id: MeterId) -> Res<Self> {
+        let last = read_cpu_times(BufReader::new(File::open(PROC_STAT)?))?;
+        Ok(Self { id, last })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(id: MeterId) -> Res<Self> {
         Ok(Self {
             id,
             cpc: cpu::CpuPercentCollector::new()?,
         })
     }
+
+    // Compute the usage since the last sample, reading /proc/stat
+    // contents from the given reader.
+    #[cfg(target_os = "linux")]
+    fn measure_from(&mut self, reader: impl BufRead) -> Res<f32> {
+        let (total, idle) = read_cpu_times(reader)?;
+        let (last_total, last_idle) = self.last;
+        self.last = (total, idle);
+        if total <= last_total {
+            return Ok(0.0);
+        }
+        let busy = (total - last_total).saturating_sub(idle.saturating_sub(last_idle));
+        Ok(busy as f32 * 100.0 / (total - last_total) as f32)
+    }
 }
 
 impl Meter for CpuPercentage {
-    fn id(&self) -> u64 {
+    fn id(&self) -> MeterId {
         self.id
     }
 
+    #[cfg(target_os = "linux")]
+    fn measure(&mut self) -> Res<f32> {
+        self.measure_from(BufReader::new(File::open(PROC_STAT)?))
+    }
+
+    #[cfg(not(target_os = "linux"))]
     fn measure(&mut self) -> Res<f32> {
         let val: f32 = self.cpc.cpu_percent()?;
         Ok(val)
     }
+
+    fn name(&self) -> &str {
+        "CPU usage"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+}
+
+// Get the total and idle times from the aggregate cpu line.
+#[cfg(target_os = "linux")]
+fn read_cpu_times(reader: impl BufRead) -> Res<(u64, u64)> {
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(fields) = line.strip_prefix("cpu ") {
+            return parse_cpu_times(fields.split_whitespace());
+        }
+    }
+    Err("cpu line not found in /proc/stat".into())
+}
+
+// Parse the time fields of a cpu line into total and idle times.
+fn parse_cpu_times<'a>(fields: impl Iterator<Item = &'a str>) -> Res<(u64, u64)> {
+    let fields = fields
+        .take(8)
+        .map(|v| v.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if fields.len() < 5 {
+        return Err("invalid /proc/stat".into());
+    }
+    let total: u64 = fields.iter().sum();
+    let idle = fields[3] + fields[4]; // idle + iowait
+    Ok((total, idle))
 }
 
 // CPU temperature
 
+#[cfg(target_os = "linux")]
+const HWMON_DIR: &str = "/sys/class/hwmon";
+
+// Sensor drivers and the label of the temperature input to use, if the
+// driver has more than one.
+#[cfg(target_os = "linux")]
+const CPU_SENSORS: &[(&str, Option<&str>)] = &[
+    ("k10temp", Some("Tccd1")),
+    ("k10temp", Some("Tctl")),
+    ("zenpower", Some("Tdie")),
+    ("coretemp", Some("Package id 0")),
+    ("cpu_thermal", None),
+];
+
 #[derive(Debug)]
 pub struct CpuTemperature {
-    pub id: u64,
+    pub id: MeterId,
+    #[cfg(target_os = "linux")]
+    input: Option<PathBuf>,
 }
 
 impl CpuTemperature {
-    pub fn new(id: u64) -> Res<Self> {
+    #[cfg(target_os = "linux")]
+    pub fn new(id: MeterId) -> Res<Self> {
+        let input = find_temp_input();
+        match &input {
+            Some(path) => tracing::debug!("CPU temperature input: {}", path.display()),
+            None => tracing::warn!("CPU temperature sensor not found"),
+        }
+        Ok(Self { id, input })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(id: MeterId) -> Res<Self> {
         Ok(Self { id })
     }
 }
 
 impl Meter for CpuTemperature {
-    fn id(&self) -> u64 {
+    fn id(&self) -> MeterId {
         self.id
     }
 
+    #[cfg(target_os = "linux")]
+    fn measure(&mut self) -> Res<f32> {
+        match &self.input {
+            Some(path) => read_temperature(File::open(path)?),
+            None => Ok(0.0),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
     fn measure(&mut self) -> Res<f32> {
         let temps = sensors::temperatures();
         for temp in temps {
@@ -66,4 +182,312 @@ impl Meter for CpuTemperature {
 
         Ok(0.0)
     }
+
+    fn name(&self) -> &str {
+        "CPU temperature"
+    }
+
+    fn unit(&self) -> &str {
+        "°C"
+    }
+}
+
+// Find the hwmon temperature input of the first known CPU sensor.
+#[cfg(target_os = "linux")]
+fn find_temp_input() -> Option<PathBuf> {
+    let mut hwmons: Vec<PathBuf> = fs::read_dir(HWMON_DIR)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    hwmons.sort();
+
+    for &(driver, label) in CPU_SENSORS {
+        for hwmon in &hwmons {
+            let name = fs::read_to_string(hwmon.join("name")).unwrap_or_default();
+            if name.trim() != driver {
+                continue;
+            }
+            let Some(label) = label else {
+                return Some(hwmon.join("temp1_input"));
+            };
+            for i in 1..=32 {
+                let path = hwmon.join(format!("temp{i}_label"));
+                if fs::read_to_string(path).is_ok_and(|s| s.trim() == label) {
+                    return Some(hwmon.join(format!("temp{i}_input")));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Read a hwmon temperature input, given in millidegrees Celsius.
+#[cfg(target_os = "linux")]
+fn read_temperature(mut reader: impl Read) -> Res<f32> {
+    let mut s = String::new();
+    reader.read_to_string(&mut s)?;
+    let millidegrees = s.trim().parse::<i64>()?;
+    Ok(millidegrees as f32 / 1000.0)
+}
+
+// CPU clock frequency
+
+#[derive(Debug)]
+pub struct CpuFreqMhz {
+    pub id: MeterId,
+    path: String,
+    max_mhz: f32,
+}
+
+impl CpuFreqMhz {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        let core = match source {
+            Some(s) => s.trim().parse::<usize>()?,
+            None => 0,
+        };
+        let max_path = format!("/sys/devices/system/cpu/cpu{core}/cpufreq/cpuinfo_max_freq");
+        let max_khz = fs::read_to_string(max_path)
+            .ok()
+            .and_then(|s| s.trim().parse::<f32>().ok())
+            .unwrap_or(5_000_000.0);
+        Ok(Self {
+            id,
+            path: format!("/sys/devices/system/cpu/cpu{core}/cpufreq/scaling_cur_freq"),
+            max_mhz: max_khz / 1000.0,
+        })
+    }
+}
+
+impl Meter for CpuFreqMhz {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let khz = fs::read_to_string(&self.path)?.trim().parse::<f32>()?;
+        Ok(khz / 1000.0)
+    }
+
+    fn name(&self) -> &str {
+        "CPU frequency"
+    }
+
+    fn unit(&self) -> &str {
+        "MHz"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, self.max_mhz)
+    }
+}
+
+// Per-core CPU percentage
+
+const CORE_SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+
+static CORE_PERCENTAGES: OnceLock<Arc<Mutex<Vec<f32>>>> = OnceLock::new();
+
+// Get the shared per-core percentages, starting the reader thread on
+// first use so all core meters share a single pass over /proc/stat.
+fn core_percentages() -> Arc<Mutex<Vec<f32>>> {
+    CORE_PERCENTAGES
+        .get_or_init(|| {
+            let shared = Arc::new(Mutex::new(Vec::new()));
+            let values = shared.clone();
+            thread::spawn(move || update_core_percentages(values));
+            shared
+        })
+        .clone()
+}
+
+fn update_core_percentages(values: Arc<Mutex<Vec<f32>>>) {
+    let mut last = Vec::<(u64, u64)>::new();
+    loop {
+        match read_core_times() {
+            Ok(times) => {
+                let pct = times
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(total, idle))| match last.get(i) {
+                        Some(&(last_total, last_idle)) if total > last_total => {
+                            let dt = (total - last_total) as f32;
+                            let di = idle.saturating_sub(last_idle) as f32;
+                            (dt - di) * 100.0 / dt
+                        }
+                        _ => 0.0,
+                    })
+                    .collect();
+                if let Ok(mut v) = values.lock() {
+                    *v = pct;
+                }
+                last = times;
+            }
+            Err(err) => {
+                tracing::warn!("cannot read core times: {err}");
+            }
+        }
+        thread::sleep(CORE_SAMPLE_PERIOD);
+    }
+}
+
+// Read (total, idle) jiffies for each core from /proc/stat.
+fn read_core_times() -> Res<Vec<(u64, u64)>> {
+    let stat = fs::read_to_string("/proc/stat")?;
+    let mut times = Vec::new();
+    for line in stat.lines() {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some(name) if name.len() > 3 && name.starts_with("cpu") => (),
+            _ => continue,
+        }
+        times.push(parse_cpu_times(parts)?);
+    }
+    Ok(times)
+}
+
+#[derive(Debug)]
+pub struct CpuCorePercentage {
+    pub id: MeterId,
+    core: usize,
+    values: Arc<Mutex<Vec<f32>>>,
+}
+
+impl CpuCorePercentage {
+    pub fn new(id: MeterId, core: usize) -> Res<Self> {
+        Ok(Self {
+            id,
+            core,
+            values: core_percentages(),
+        })
+    }
+}
+
+impl Meter for CpuCorePercentage {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let values = match self.values.lock() {
+            Ok(v) => v,
+            Err(_) => return Err("core percentages unavailable".into()),
+        };
+
+        // Not sampled yet.
+        if values.is_empty() {
+            return Ok(0.0);
+        }
+
+        match values.get(self.core) {
+            Some(val) => Ok(*val),
+            None => Err(format!("core {} not found", self.core).into()),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "CPU core usage"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+}
+
+// Per-core CPU temperature
+
+#[derive(Debug)]
+pub struct CpuCoreTemperature {
+    pub id: MeterId,
+    label: String,
+}
+
+impl CpuCoreTemperature {
+    pub fn new(id: MeterId, core: usize) -> Res<Self> {
+        Ok(Self {
+            id,
+            label: format!("Core {core}"),
+        })
+    }
+}
+
+impl Meter for CpuCoreTemperature {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let mut package = None;
+        for t in sensors::temperatures().into_iter().flatten() {
+            if t.label() == Some(self.label.as_str()) {
+                return Ok(t.current().celsius() as f32);
+            }
+
+            // Remember the package temperature in case there's no per-core data.
+            let is_package = (t.unit() == "k10temp" && t.label() == Some("Tccd1"))
+                || (t.unit() == "coretemp" && t.label() == Some("Package id 0"));
+            if package.is_none() && is_package {
+                package = Some(t.current().celsius() as f32);
+            }
+        }
+
+        Ok(package.unwrap_or(0.0))
+    }
+
+    fn name(&self) -> &str {
+        "CPU core temperature"
+    }
+
+    fn unit(&self) -> &str {
+        "°C"
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    const PROC_STAT_SAMPLE: &str = include_str!("../tests/fixtures/proc_stat");
+    const PROC_STAT_LATER: &str = include_str!("../tests/fixtures/proc_stat_later");
+
+    #[test]
+    fn test_read_cpu_times() {
+        let (total, idle) = read_cpu_times(PROC_STAT_SAMPLE.as_bytes()).unwrap();
+        assert_eq!(total, 22637015);
+        assert_eq!(idle, 22631853);
+    }
+
+    #[test]
+    fn test_read_cpu_times_missing() {
+        assert!(read_cpu_times("intr 1 2 3\n".as_bytes()).is_err());
+        assert!(read_cpu_times("cpu  1 2 3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_temperature() {
+        assert_eq!(read_temperature("45000\n".as_bytes()).unwrap(), 45.0);
+        assert_eq!(read_temperature("67125".as_bytes()).unwrap(), 67.125);
+        assert_eq!(read_temperature("-5000\n".as_bytes()).unwrap(), -5.0);
+    }
+
+    #[test]
+    fn test_read_temperature_invalid() {
+        assert!(read_temperature("".as_bytes()).is_err());
+        assert!(read_temperature("hot\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_cpu_percentage() {
+        let mut meter = CpuPercentage {
+            id: MeterId::from_name("CPU:PERCENTAGE"),
+            last: read_cpu_times(PROC_STAT_SAMPLE.as_bytes()).unwrap(),
+        };
+        // 400 ticks elapsed, 200 of them idle.
+        let pct = meter.measure_from(PROC_STAT_LATER.as_bytes()).unwrap();
+        assert_eq!(pct, 50.0);
+
+        // No time elapsed since the last sample.
+        let pct = meter.measure_from(PROC_STAT_LATER.as_bytes()).unwrap();
+        assert_eq!(pct, 0.0);
+    }
 }
@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use serialport::SerialPortType;
+
+use crate::Res;
+
+// USB vendor and product IDs of known Turing Smart Screen models.
+const SCREEN_IDS: [(u16, u16); 2] = [
+    (0x1a86, 0x5722), // Turing Smart Screen 3.5"
+    (0x1d6b, 0x0121), // XuanFang 3.5"
+];
+
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub port: String,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+}
+
+// Find all connected screens.
+pub fn enumerate() -> Res<Vec<Device>> {
+    let mut res = Vec::new();
+    for p in serialport::available_ports()? {
+        if let SerialPortType::UsbPort(usb) = p.port_type {
+            if SCREEN_IDS.contains(&(usb.vid, usb.pid)) {
+                res.push(Device {
+                    port: p.port_name,
+                    serial_number: usb.serial_number,
+                    manufacturer: usb.manufacturer,
+                });
+            }
+        }
+    }
+    Ok(res)
+}
+
+// Find the serial port of the screen with the given serial number.
+pub fn find_port(serial_number: &str) -> Res<String> {
+    enumerate()?
+        .into_iter()
+        .find(|dev| dev.serial_number.as_deref() == Some(serial_number))
+        .map(|dev| dev.port)
+        .ok_or_else(|| format!("screen not found: {serial_number}").into())
+}
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use nix::sys::statvfs::statvfs;
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+const SECTOR_SIZE: f32 = 512.0;
+
+// Top of the throughput scale, about what a SATA SSD sustains.
+const MAX_MBPS: f32 = 550.0;
+
+// Field indices in /proc/diskstats lines.
+const SECTORS_READ: usize = 5;
+const SECTORS_WRITTEN: usize = 9;
+
+// Find the block device backing the root filesystem.
+fn root_device() -> Res<String> {
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    for line in mounts.lines() {
+        let mut parts = line.split_whitespace();
+        if let (Some(dev), Some("/")) = (parts.next(), parts.next()) {
+            // Resolve symlinks such as /dev/root or /dev/mapper/*.
+            let path = fs::canonicalize(dev).unwrap_or_else(|_| Path::new(dev).to_path_buf());
+            if let Some(name) = path.file_name() {
+                return Ok(name.to_string_lossy().to_string());
+            }
+        }
+    }
+    Err("cannot find root device".into())
+}
+
+fn read_sectors(device: &str, field: usize) -> Res<u64> {
+    let stats = fs::read_to_string("/proc/diskstats")?;
+    for line in stats.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() > field && parts[2] == device {
+            return Ok(parts[field].parse::<u64>()?);
+        }
+    }
+    Err(format!("device not found: {device}").into())
+}
+
+// Throughput computed from the difference between two samples.
+#[derive(Debug)]
+struct Throughput {
+    device: String,
+    field: usize,
+    last: Option<(Instant, u64)>,
+}
+
+impl Throughput {
+    fn new(source: Option<&str>, field: usize) -> Res<Self> {
+        let device = match source {
+            Some(dev) => dev.to_string(),
+            None => root_device()?,
+        };
+        Ok(Self {
+            device,
+            field,
+            last: None,
+        })
+    }
+
+    fn mbps(&mut self) -> Res<f32> {
+        let now = Instant::now();
+        let sectors = read_sectors(&self.device, self.field)?;
+        let val = match self.last {
+            Some((then, last_sectors)) => {
+                let secs = now.duration_since(then).as_secs_f32();
+                if secs > 0.0 {
+                    let bytes = sectors.saturating_sub(last_sectors) as f32 * SECTOR_SIZE;
+                    bytes / secs / 1_000_000.0
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        };
+        self.last = Some((now, sectors));
+        Ok(val)
+    }
+}
+
+// Disk read throughput
+
+#[derive(Debug)]
+pub struct DiskReadMbps {
+    pub id: MeterId,
+    throughput: Throughput,
+}
+
+impl DiskReadMbps {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        Ok(Self {
+            id,
+            throughput: Throughput::new(source, SECTORS_READ)?,
+        })
+    }
+}
+
+impl Meter for DiskReadMbps {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        self.throughput.mbps()
+    }
+
+    fn name(&self) -> &str {
+        "Disk read rate"
+    }
+
+    fn unit(&self) -> &str {
+        "MB/s"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, MAX_MBPS)
+    }
+}
+
+// Disk write throughput
+
+#[derive(Debug)]
+pub struct DiskWriteMbps {
+    pub id: MeterId,
+    throughput: Throughput,
+}
+
+impl DiskWriteMbps {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        Ok(Self {
+            id,
+            throughput: Throughput::new(source, SECTORS_WRITTEN)?,
+        })
+    }
+}
+
+impl Meter for DiskWriteMbps {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        self.throughput.mbps()
+    }
+
+    fn name(&self) -> &str {
+        "Disk write rate"
+    }
+
+    fn unit(&self) -> &str {
+        "MB/s"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, MAX_MBPS)
+    }
+}
+
+// Disk space used percentage
+
+#[derive(Debug)]
+pub struct DiskUsedPercent {
+    pub id: MeterId,
+    mount_point: String,
+}
+
+impl DiskUsedPercent {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        Ok(Self {
+            id,
+            mount_point: source.unwrap_or("/").to_string(),
+        })
+    }
+}
+
+impl Meter for DiskUsedPercent {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let st = statvfs(self.mount_point.as_str())?;
+        let total = st.blocks() as f32;
+        if total == 0.0 {
+            return Ok(0.0);
+        }
+        let free = st.blocks_free() as f32;
+        Ok((total - free) * 100.0 / total)
+    }
+
+    fn name(&self) -> &str {
+        "Disk used"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+}
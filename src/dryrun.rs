@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::sync::{Arc, Mutex};
+
+use turing_screen::{Rgba, Screen};
+
+use crate::Res;
+
+// Screen replacement that draws into memory. Frames are written to PNG
+// files by the matching FrameCapture.
+pub fn new(width: usize, height: usize, prefix: &str) -> (Box<dyn Screen>, FrameCapture) {
+    let canvas = Arc::new(Mutex::new(vec![Rgba::new(0, 0, 0, 0xff); width * height]));
+    let scr = PngScreen {
+        canvas: canvas.clone(),
+        width,
+        height,
+    };
+    let capture = FrameCapture {
+        canvas,
+        width,
+        height,
+        prefix: prefix.to_string(),
+        count: 0,
+    };
+    (Box::new(scr), capture)
+}
+
+struct PngScreen {
+    canvas: Arc<Mutex<Vec<Rgba>>>,
+    width: usize,
+    height: usize,
+}
+
+impl Screen for PngScreen {
+    fn init(&mut self) -> Res<()> {
+        Ok(())
+    }
+
+    fn screen_on(&mut self) -> Res<()> {
+        Ok(())
+    }
+
+    fn screen_off(&mut self) -> Res<()> {
+        Ok(())
+    }
+
+    fn set_brightness(&mut self, _level: i32) -> Res<()> {
+        Ok(())
+    }
+
+    fn screen_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    // Bitmaps are sent to the device as little-endian RGB565.
+    fn draw_bitmap(
+        &mut self,
+        data: &[u8],
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Res<()> {
+        let mut canvas = self.canvas.lock().unwrap();
+        for (i, px) in data.chunks_exact(2).take(width * height).enumerate() {
+            let (col, row) = (x + i % width, y + i / width);
+            if col >= self.width || row >= self.height {
+                continue;
+            }
+            canvas[row * self.width + col] = rgb565_to_rgba(u16::from_le_bytes([px[0], px[1]]));
+        }
+        Ok(())
+    }
+}
+
+// Expand a RGB565 pixel to 8 bits per channel.
+pub fn rgb565_to_rgba(val: u16) -> Rgba {
+    let r = ((val >> 11) & 0x1f) as u8;
+    let g = ((val >> 5) & 0x3f) as u8;
+    let b = (val & 0x1f) as u8;
+    Rgba::new(r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2, 0xff)
+}
+
+pub struct FrameCapture {
+    canvas: Arc<Mutex<Vec<Rgba>>>,
+    width: usize,
+    height: usize,
+    prefix: String,
+    count: usize,
+}
+
+impl FrameCapture {
+    // Save the current screen contents as <prefix>_NNN.png.
+    pub fn save_frame(&mut self) -> Res<()> {
+        let path = format!("{}_{:03}.png", self.prefix, self.count);
+        let canvas = self.canvas.lock().unwrap();
+        lodepng::encode32_file(&path, &canvas[..], self.width, self.height)?;
+        tracing::debug!("saved frame {path}");
+        self.count += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+// Name prefix of meters that run a command.
+pub const PREFIX: &str = "CMD:";
+
+// Commands taking longer than this are killed.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Value printed by a shell command. Commands run in their own thread
+// so a slow one doesn't hold up other meters; each measurement starts
+// the command unless it's still running and reports the last result.
+#[derive(Debug)]
+pub struct ExecMeter {
+    pub id: MeterId,
+    command: Arc<String>,
+    last: Arc<Mutex<Result<f32, String>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl ExecMeter {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        match source {
+            Some(command) => Ok(Self {
+                id,
+                command: Arc::new(command.to_string()),
+                last: Arc::new(Mutex::new(Err("no output from command yet".to_string()))),
+                running: Arc::new(AtomicBool::new(false)),
+            }),
+            None => Err("command meter requires a source".into()),
+        }
+    }
+}
+
+impl Meter for ExecMeter {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        if !self.running.swap(true, Ordering::AcqRel) {
+            let (command, last, running) = (
+                self.command.clone(),
+                self.last.clone(),
+                self.running.clone(),
+            );
+            thread::spawn(move || {
+                let res = run(&command).map_err(|err| err.to_string());
+                *last.lock().unwrap() = res;
+                running.store(false, Ordering::Release);
+            });
+        }
+        let last = self.last.lock().unwrap().clone();
+        Ok(last?)
+    }
+}
+
+// Run a command with sh and parse the first word of its output.
+fn run(command: &str) -> Res<f32> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Read the output while waiting, a command filling the pipe would
+    // block otherwise.
+    let mut stdout = child.stdout.take().ok_or("cannot read command output")?;
+    let reader = thread::spawn(move || {
+        let mut out = String::new();
+        stdout.read_to_string(&mut out).map(|_| out)
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("command timed out: {command}").into());
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    if !status.success() {
+        return Err(format!("command failed ({status}): {command}").into());
+    }
+
+    let out = reader.join().map_err(|_| "cannot read command output")??;
+    match out.split_whitespace().next() {
+        Some(word) => Ok(word.parse::<f32>()?),
+        None => Err(format!("no output from command: {command}").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        assert_eq!(run("echo 42.5").unwrap(), 42.5);
+        assert_eq!(run("printf ' 7\\n8\\n'").unwrap(), 7.0);
+    }
+
+    #[test]
+    fn test_run_large_output() {
+        // More than fits in a pipe buffer.
+        let command = "printf '3 '; head -c 200000 /dev/zero | tr '\\0' x";
+        assert_eq!(run(command).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_measure_in_background() {
+        // The first measurement starts the command.
+        let mut meter = ExecMeter::new(MeterId(0), Some("echo 5")).unwrap();
+        let _ = meter.measure();
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(meter.measure().unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_run_errors() {
+        assert!(run("false").is_err());
+        assert!(run("true").is_err());
+        assert!(run("echo abc").is_err());
+    }
+}
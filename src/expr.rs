@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::Res;
+
+// Limits keeping parsing and evaluation from overflowing the stack.
+const MAX_DEPTH: usize = 64;
+const MAX_LENGTH: usize = 1024;
+
+// Arithmetic expression on the meter value x, such as "x * 9/5 + 32".
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Num(f32),
+    Var,
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Res<Expr> {
+        if s.len() > MAX_LENGTH {
+            return Err(format!("expression longer than {MAX_LENGTH} characters").into());
+        }
+        let mut parser = Parser {
+            chars: s.chars().peekable(),
+            depth: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_spaces();
+        match parser.chars.next() {
+            Some(c) => Err(format!("unexpected '{c}' in expression '{s}'").into()),
+            None => Ok(expr),
+        }
+    }
+
+    pub fn eval(&self, x: f32) -> f32 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Var => x,
+            Expr::Neg(a) => -a.eval(x),
+            Expr::Add(a, b) => a.eval(x) + b.eval(x),
+            Expr::Sub(a, b) => a.eval(x) - b.eval(x),
+            Expr::Mul(a, b) => a.eval(x) * b.eval(x),
+            Expr::Div(a, b) => a.eval(x) / b.eval(x),
+        }
+    }
+}
+
+// Recursive descent parser:
+//   expr   = term { ("+" | "-") term }
+//   term   = factor { ("*" | "/") factor }
+//   factor = "-" factor | "(" expr ")" | number | "x"
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    depth: usize, // nesting of factors being parsed
+}
+
+impl Parser<'_> {
+    fn skip_spaces(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_spaces();
+        self.chars.peek().copied()
+    }
+
+    fn expr(&mut self) -> Res<Expr> {
+        let mut lhs = self.term()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.chars.next();
+                    lhs = Expr::Add(Box::new(lhs), Box::new(self.term()?));
+                }
+                Some('-') => {
+                    self.chars.next();
+                    lhs = Expr::Sub(Box::new(lhs), Box::new(self.term()?));
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Res<Expr> {
+        let mut lhs = self.factor()?;
+        loop {
+            match self.peek() {
+                Some('*') => {
+                    self.chars.next();
+                    lhs = Expr::Mul(Box::new(lhs), Box::new(self.factor()?));
+                }
+                Some('/') => {
+                    self.chars.next();
+                    lhs = Expr::Div(Box::new(lhs), Box::new(self.factor()?));
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Res<Expr> {
+        if self.depth == MAX_DEPTH {
+            return Err("expression nested too deeply".into());
+        }
+        self.depth += 1;
+        let res = self.primary();
+        self.depth -= 1;
+        res
+    }
+
+    fn primary(&mut self) -> Res<Expr> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.chars.next();
+                let expr = self.expr()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.chars.next();
+                        Ok(expr)
+                    }
+                    _ => Err("missing ')' in expression".into()),
+                }
+            }
+            Some('x') => {
+                self.chars.next();
+                Ok(Expr::Var)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut num = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    num.push(c);
+                }
+                Ok(Expr::Num(num.parse()?))
+            }
+            Some(c) => Err(format!("unexpected '{c}' in expression").into()),
+            None => Err("unexpected end of expression".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(s: &str, x: f32) -> f32 {
+        Expr::parse(s).unwrap().eval(x)
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("x * 9/5 + 32", 100.0), 212.0);
+        assert_eq!(eval("1 + 2 * 3", 0.0), 7.0);
+        assert_eq!(eval("10 - 4 - 3", 0.0), 3.0);
+        assert_eq!(eval("8 / 4 / 2", 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_unary_minus_and_parentheses() {
+        assert_eq!(eval("-x", 3.0), -3.0);
+        assert_eq!(eval("--x", 3.0), 3.0);
+        assert_eq!(eval("2 * -x", 3.0), -6.0);
+        assert_eq!(eval("(1 + 2) * 3", 0.0), 9.0);
+        assert_eq!(eval("-(x - 1) * ((2))", 4.0), -6.0);
+        assert_eq!(eval(" x ", 1.5), 1.5);
+        assert_eq!(eval(".5 * x", 4.0), 2.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        for s in [
+            "1..2", "(x + 1", "x 1", "x + 1)", "", " ", "x +", "y", "2 * * x",
+        ] {
+            assert!(Expr::parse(s).is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let deep = format!("{}x{}", "(".repeat(500), ")".repeat(500));
+        assert!(Expr::parse(&deep).is_err());
+        assert!(Expr::parse(&"-".repeat(1000)).is_err());
+        let nested = format!("{}x{}", "(".repeat(10), ")".repeat(10));
+        assert_eq!(eval(&nested, 2.0), 2.0);
+    }
+}
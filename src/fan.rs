@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+// Find all hwmon fan inputs, sorted by hwmon node.
+fn fan_inputs() -> Res<Vec<PathBuf>> {
+    let mut fans = Vec::new();
+    for entry in fs::read_dir("/sys/class/hwmon")? {
+        let path = entry?.path().join("fan1_input");
+        if path.exists() {
+            fans.push(path);
+        }
+    }
+    fans.sort();
+    Ok(fans)
+}
+
+// Speed used as the top of the scale when the fan doesn't report one.
+const DEFAULT_MAX_RPM: f32 = 5000.0;
+
+// Fan speed
+
+#[derive(Debug)]
+pub struct FanRpm {
+    pub id: MeterId,
+    index: usize,
+    max_rpm: f32,
+}
+
+impl FanRpm {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        let index = match source {
+            Some(s) => s.trim().parse::<usize>()?,
+            None => 0,
+        };
+        let max_rpm = fan_inputs()
+            .ok()
+            .and_then(|fans| {
+                let path = fans.get(index)?.with_file_name("fan1_max");
+                fs::read_to_string(path).ok()?.trim().parse::<f32>().ok()
+            })
+            .filter(|&rpm| rpm > 0.0)
+            .unwrap_or(DEFAULT_MAX_RPM);
+        Ok(Self { id, index, max_rpm })
+    }
+}
+
+impl Meter for FanRpm {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let fans = fan_inputs()?;
+        match fans.get(self.index) {
+            Some(path) => Ok(fs::read_to_string(path)?.trim().parse::<f32>()?),
+            None => Err(format!("fan {} not found", self.index).into()),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Fan speed"
+    }
+
+    fn unit(&self) -> &str {
+        "RPM"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, self.max_rpm)
+    }
+}
@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+// Name prefix of meters that read a file.
+pub const PREFIX: &str = "FILE:";
+
+// Value stored in a file, such as a tmpfs file written by another program.
+#[derive(Debug)]
+pub struct FileMeter {
+    pub id: MeterId,
+    path: PathBuf,
+}
+
+impl FileMeter {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        match source {
+            Some(path) => Ok(Self {
+                id,
+                path: PathBuf::from(path),
+            }),
+            None => Err("file meter requires a source".into()),
+        }
+    }
+}
+
+impl Meter for FileMeter {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let data = fs::read_to_string(&self.path)?;
+        match data.split_whitespace().next() {
+            Some(word) => Ok(word.parse::<f32>()?),
+            None => Err(format!("{} is empty", self.path.display()).into()),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use rusttype::{point, Scale};
+use turing_screen::{Coord, Image, Rect, Rgba};
+
+use crate::framebuffer::source_over;
+use crate::Res;
+
+pub struct Font {
+    font: rusttype::Font<'static>,
+}
+
+impl Font {
+    pub fn from_data(data: Vec<u8>) -> Res<Self> {
+        let font = rusttype::Font::try_from_vec(data).ok_or("invalid font data")?;
+        Ok(Self { font })
+    }
+
+    // Rasterize the printable ASCII characters, other characters are
+    // added to the cache when first drawn.
+    pub fn prerender_ascii(&self, size: f32) -> GlyphCache {
+        let scale = Scale::uniform(size);
+        let v_metrics = self.font.v_metrics(scale);
+        let mut cache = GlyphCache {
+            font: self.font.clone(),
+            scale,
+            ascent: v_metrics.ascent,
+            height: (v_metrics.ascent - v_metrics.descent).ceil() as usize,
+            glyphs: HashMap::new(),
+        };
+        for c in ' '..='~' {
+            cache.glyph(c);
+        }
+        cache
+    }
+}
+
+// Glyph bitmaps of a font at a given size.
+pub struct GlyphCache {
+    font: rusttype::Font<'static>,
+    scale: Scale,
+    ascent: f32,
+    height: usize,
+    glyphs: HashMap<char, Glyph>,
+}
+
+struct Glyph {
+    advance: f32,
+    left: i32, // offset from the pen position
+    top: i32,  // offset from the baseline
+    width: usize,
+    coverage: Vec<f32>,
+}
+
+impl GlyphCache {
+    // Distance from the top of the text to the baseline.
+    pub fn ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    fn glyph(&mut self, c: char) -> &Glyph {
+        let (font, scale) = (&self.font, self.scale);
+        self.glyphs.entry(c).or_insert_with(|| {
+            let glyph = font.glyph(c).scaled(scale);
+            let advance = glyph.h_metrics().advance_width;
+            let glyph = glyph.positioned(point(0.0, 0.0));
+            let Some(bb) = glyph.pixel_bounding_box() else {
+                return Glyph {
+                    advance,
+                    left: 0,
+                    top: 0,
+                    width: 0,
+                    coverage: Vec::new(),
+                };
+            };
+            let width = bb.width() as usize;
+            let mut coverage = vec![0.0; width * bb.height() as usize];
+            glyph.draw(|x, y, v| coverage[y as usize * width + x as usize] = v);
+            Glyph {
+                advance,
+                left: bb.min.x,
+                top: bb.min.y,
+                width,
+                coverage,
+            }
+        })
+    }
+}
+
+// Draw text over the background. Returns the text image and the area it
+// covers on the screen, starting at the given position. Text is laid out
+// by character, glyphs missing from the cache are rasterized on first use.
+// Right-to-left text places the first character rightmost. Anti-aliased
+// text blends partially covered pixels, otherwise they're either set to
+// the text color or left alone.
+pub fn draw_text(
+    cache: &mut GlyphCache,
+    bg: &Image,
+    color: Rgba,
+    pos: &Coord,
+    s: &str,
+    rtl: bool,
+    antialiased: bool,
+) -> (Image, Rect) {
+    let (x0, y0) = (pos.x, pos.y);
+
+    // Lay out the glyphs first to know the text width.
+    let mut layout = Vec::new();
+    let mut pen = 0.0;
+    let mut prev = None;
+    let chars: Vec<char> = if rtl {
+        s.chars().rev().collect()
+    } else {
+        s.chars().collect()
+    };
+    for c in chars {
+        if let Some(prev) = prev {
+            pen += cache.font.pair_kerning(cache.scale, prev, c);
+        }
+        layout.push((c, pen));
+        pen += cache.glyph(c).advance;
+        prev = Some(c);
+    }
+
+    let width = (pen.ceil() as usize).min(bg.width.saturating_sub(x0));
+    let height = cache.height.min(bg.height.saturating_sub(y0));
+    let mut img = Image::new(width, height);
+    for row in 0..height {
+        let src = (y0 + row) * bg.width + x0;
+        img.buffer[row * width..(row + 1) * width].copy_from_slice(&bg.buffer[src..src + width]);
+    }
+
+    let baseline = cache.ascent.round() as i32;
+    for (c, pen) in layout {
+        let glyph = &cache.glyphs[&c];
+        let gx = pen.round() as i32 + glyph.left;
+        let gy = baseline + glyph.top;
+        for (i, &v) in glyph.coverage.iter().enumerate() {
+            let x = gx + (i % glyph.width) as i32;
+            let y = gy + (i / glyph.width) as i32;
+            if v <= 0.0 || x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+                continue;
+            }
+            let coverage = if antialiased {
+                v.min(1.0)
+            } else if v >= 0.5 {
+                1.0
+            } else {
+                continue;
+            };
+            let dst = &mut img.buffer[y as usize * width + x as usize];
+            let alpha = (coverage * color.a as f32).round() as u8;
+            *dst = source_over(Rgba::new(color.r, color.g, color.b, alpha), *dst);
+        }
+    }
+
+    (img, Rect::new(x0, y0, width, height))
+}
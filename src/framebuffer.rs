@@ -0,0 +1,514 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+
+use turing_screen::{Coord, Image, Rect, Rgba, Screen};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::Res;
+
+// Size of the tiles compared to find what changed on screen.
+const TILE_SIZE: usize = 8;
+
+// The composited screen contents. Widgets draw here and the renderer
+// flushes the changed area to the device at the end of each frame.
+pub struct Framebuffer {
+    img: Image,
+    tiles: Vec<u64>, // hashes of the tiles last sent to the screen
+    tile_cols: usize,
+    rotation: u16, // clockwise degrees from the framebuffer to the screen
+    flip_h: bool,
+    flip_v: bool,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let tile_cols = width.div_ceil(TILE_SIZE);
+        let tile_rows = height.div_ceil(TILE_SIZE);
+        Self {
+            img: Image::new(width, height),
+            tiles: vec![0; tile_cols * tile_rows],
+            tile_cols,
+            rotation: 0,
+            flip_h: false,
+            flip_v: false,
+        }
+    }
+
+    // Rotate the contents when sending them to the screen. Drawing
+    // still uses the unrotated coordinates.
+    pub fn set_rotation(&mut self, degrees: u16) {
+        self.rotation = degrees;
+    }
+
+    pub fn rotation(&self) -> u16 {
+        self.rotation
+    }
+
+    // Mirror the contents when sending them to the screen, before they
+    // are rotated.
+    pub fn set_flip(&mut self, horizontal: bool, vertical: bool) {
+        self.flip_h = horizontal;
+        self.flip_v = vertical;
+    }
+
+    // Find the area that differs from a previous snapshot. Framebuffers
+    // of different sizes differ everywhere.
+    pub fn diff(&self, previous: &Framebuffer) -> FramebufferDiff {
+        let (width, height) = (self.img.width, self.img.height);
+        if previous.img.width != width || previous.img.height != height {
+            return FramebufferDiff::full(width, height);
+        }
+
+        let mut diff = FramebufferDiff::default();
+        let rows = self
+            .img
+            .buffer
+            .chunks_exact(width)
+            .zip(previous.img.buffer.chunks_exact(width));
+        for (y, (row, prev)) in rows.enumerate() {
+            let first = match row.iter().zip(prev).position(|(a, b)| a != b) {
+                Some(x) => x,
+                None => continue,
+            };
+            let last = row.iter().zip(prev).rposition(|(a, b)| a != b).unwrap();
+            diff.add(first, last, y);
+        }
+        diff
+    }
+
+    // Record the current contents as what the screen shows, after it
+    // was drawn by other means.
+    pub fn mark_clean(&mut self) {
+        for i in 0..self.tiles.len() {
+            let (col, row) = (i % self.tile_cols, i / self.tile_cols);
+            self.tiles[i] = self.tile_hash(col, row);
+        }
+    }
+
+    // Send the tiles inside the given area that changed since they were
+    // last sent. Adjacent changed tiles in a row are sent together.
+    pub fn render_changed(&mut self, scr: &mut Box<dyn Screen>, rect: &Rect) -> Res<()> {
+        if rect.w == 0 || rect.h == 0 {
+            return Ok(());
+        }
+        let col_end = (rect.x + rect.w).min(self.img.width).div_ceil(TILE_SIZE);
+        let row_end = (rect.y + rect.h).min(self.img.height).div_ceil(TILE_SIZE);
+
+        for row in rect.y / TILE_SIZE..row_end {
+            let mut run_start = None;
+            for col in rect.x / TILE_SIZE..=col_end {
+                let changed = col < col_end && {
+                    let hash = self.tile_hash(col, row);
+                    let tile = &mut self.tiles[row * self.tile_cols + col];
+                    let changed = *tile != hash;
+                    *tile = hash;
+                    changed
+                };
+                match (changed, run_start) {
+                    (true, None) => run_start = Some(col),
+                    (false, Some(start)) => {
+                        self.render_tiles(scr, start, col, row)?;
+                        run_start = None;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Send tiles from col_start up to but not including col_end.
+    fn render_tiles(
+        &self,
+        scr: &mut Box<dyn Screen>,
+        col_start: usize,
+        col_end: usize,
+        row: usize,
+    ) -> Res<()> {
+        let x = col_start * TILE_SIZE;
+        let y = row * TILE_SIZE;
+        let w = (col_end * TILE_SIZE).min(self.img.width) - x;
+        let h = TILE_SIZE.min(self.img.height - y);
+        self.render_area(scr, &Rect::new(x, y, w, h))
+    }
+
+    // Send the whole framebuffer and record it as what the screen shows.
+    pub fn render_all(&mut self, scr: &mut Box<dyn Screen>) -> Res<()> {
+        self.render_area(scr, &self.img.full())?;
+        self.mark_clean();
+        Ok(())
+    }
+
+    fn render_area(&self, scr: &mut Box<dyn Screen>, rect: &Rect) -> Res<()> {
+        if self.rotation == 0 && !self.flip_h && !self.flip_v {
+            return self.img.render_on(scr, rect, &Coord::new(rect.x, rect.y));
+        }
+        let (img, pos) = self.transformed(rect);
+        img.render_on(scr, &img.full(), &pos)
+    }
+
+    // Copy an area flipped and rotated to the screen orientation, with
+    // its position on the screen.
+    fn transformed(&self, rect: &Rect) -> (Image, Coord) {
+        let (width, height) = (self.img.width, self.img.height);
+        let mut img = Image::new(rect.w, rect.h);
+        img.copy_image(&self.img, rect, &Coord::new(0, 0));
+
+        let mut x = rect.x;
+        let mut y = rect.y;
+        if self.flip_h {
+            flip_image_horizontal(&mut img);
+            x = width - rect.x - rect.w;
+        }
+        if self.flip_v {
+            flip_image_vertical(&mut img);
+            y = height - rect.y - rect.h;
+        }
+
+        let pos = match self.rotation {
+            90 => {
+                rotate_image_90(&mut img);
+                Coord::new(height - y - rect.h, x)
+            }
+            180 => {
+                img.buffer.reverse();
+                Coord::new(width - x - rect.w, height - y - rect.h)
+            }
+            270 => {
+                rotate_image_270(&mut img);
+                Coord::new(y, width - x - rect.w)
+            }
+            _ => Coord::new(x, y),
+        };
+        (img, pos)
+    }
+
+    fn tile_hash(&self, col: usize, row: usize) -> u64 {
+        let x = col * TILE_SIZE;
+        let y = row * TILE_SIZE;
+        let x_end = (x + TILE_SIZE).min(self.img.width);
+        let y_end = (y + TILE_SIZE).min(self.img.height);
+        let mut hasher = Xxh3::new();
+        for y in y..y_end {
+            let start = y * self.img.width;
+            self.img.buffer[start + x..start + x_end].hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    // Rotate the contents clockwise. Width and height are swapped and
+    // every tile is sent again on the next render.
+    pub fn rotate_90(&mut self) {
+        rotate_image_90(&mut self.img);
+        self.reset_tiles();
+    }
+
+    pub fn rotate_180(&mut self) {
+        self.img.buffer.reverse();
+        self.tiles.fill(0);
+    }
+
+    pub fn rotate_270(&mut self) {
+        rotate_image_270(&mut self.img);
+        self.reset_tiles();
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        flip_image_horizontal(&mut self.img);
+        self.tiles.fill(0);
+    }
+
+    pub fn flip_vertical(&mut self) {
+        flip_image_vertical(&mut self.img);
+        self.tiles.fill(0);
+    }
+
+    fn reset_tiles(&mut self) {
+        self.tile_cols = self.img.width.div_ceil(TILE_SIZE);
+        let tile_rows = self.img.height.div_ceil(TILE_SIZE);
+        self.tiles = vec![0; self.tile_cols * tile_rows];
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Rgba) {
+        if x < self.img.width && y < self.img.height {
+            self.img.buffer[y * self.img.width + x] = color;
+        }
+    }
+
+    // Fill a rectangle with a solid color, clipped to the framebuffer.
+    pub fn fill_rect(&mut self, rect: &Rect, color: Rgba) {
+        let width = self.img.width;
+        let x_end = (rect.x + rect.w).min(width);
+        let y_end = (rect.y + rect.h).min(self.img.height);
+        if rect.x >= x_end {
+            return;
+        }
+        for y in rect.y..y_end {
+            self.img.buffer[y * width + rect.x..y * width + x_end].fill(color);
+        }
+    }
+
+    // Draw a horizontal line from x0 to x1, inclusive.
+    #[inline]
+    pub fn draw_hline(&mut self, y: usize, x0: usize, x1: usize, color: Rgba) {
+        let width = self.img.width;
+        if y >= self.img.height || x0 >= width || x1 < x0 {
+            return;
+        }
+        let start = y * width;
+        self.img.buffer[start + x0..=start + x1.min(width - 1)].fill(color);
+    }
+
+    // Draw a vertical line from y0 to y1, inclusive.
+    #[inline]
+    pub fn draw_vline(&mut self, x: usize, y0: usize, y1: usize, color: Rgba) {
+        let width = self.img.width;
+        if x >= width || y0 >= self.img.height || y1 < y0 {
+            return;
+        }
+        let y1 = y1.min(self.img.height - 1);
+        for px in self.img.buffer[y0 * width + x..=y1 * width + x]
+            .iter_mut()
+            .step_by(width)
+        {
+            *px = color;
+        }
+    }
+
+    // Draw a pixel with alpha over the current contents.
+    pub fn blend_pixel(&mut self, x: usize, y: usize, color: Rgba) {
+        if x < self.img.width && y < self.img.height {
+            let dst = &mut self.img.buffer[y * self.img.width + x];
+            *dst = source_over(color, *dst);
+        }
+    }
+
+    // Draw an image with alpha using source-over compositing.
+    pub fn composite_image(&mut self, img: &Image, pos: &Coord) {
+        for row in 0..img.height {
+            let y = pos.y + row;
+            if y >= self.img.height {
+                break;
+            }
+            for col in 0..img.width {
+                let x = pos.x + col;
+                if x >= self.img.width {
+                    break;
+                }
+                let src = img.buffer[row * img.width + col];
+                self.blend_pixel(x, y, src);
+            }
+        }
+    }
+
+    // Draw a circle outline using the midpoint algorithm.
+    pub fn draw_circle(&mut self, cx: usize, cy: usize, r: usize, color: Rgba) {
+        let (cx, cy) = (cx as isize, cy as isize);
+        let (mut x, mut y) = (r as isize, 0);
+        let mut err = 1 - x;
+
+        while x >= y {
+            for (dx, dy) in [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                let (px, py) = (cx + dx, cy + dy);
+                if px >= 0 && py >= 0 {
+                    self.set_pixel(px as usize, py as usize, color);
+                }
+            }
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    // Draw a line using Bresenham's algorithm.
+    pub fn draw_line(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: Rgba) {
+        let (mut x, mut y) = (x0 as isize, y0 as isize);
+        let (x1, y1) = (x1 as isize, y1 as isize);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = if x < x1 { 1 } else { -1 };
+        let sy = if y < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+
+        loop {
+            self.set_pixel(x as usize, y as usize, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+fn rotate_image_90(img: &mut Image) {
+    let (width, height) = (img.width, img.height);
+    let mut buffer = Vec::with_capacity(img.buffer.len());
+    for x in 0..width {
+        buffer.extend((0..height).rev().map(|y| img.buffer[y * width + x]));
+    }
+    (img.buffer, img.width, img.height) = (buffer, height, width);
+}
+
+fn rotate_image_270(img: &mut Image) {
+    let (width, height) = (img.width, img.height);
+    let mut buffer = Vec::with_capacity(img.buffer.len());
+    for x in (0..width).rev() {
+        buffer.extend((0..height).map(|y| img.buffer[y * width + x]));
+    }
+    (img.buffer, img.width, img.height) = (buffer, height, width);
+}
+
+fn flip_image_horizontal(img: &mut Image) {
+    for row in img.buffer.chunks_exact_mut(img.width) {
+        row.reverse();
+    }
+}
+
+fn flip_image_vertical(img: &mut Image) {
+    let width = img.width;
+    let height = img.height;
+    for y in 0..height / 2 {
+        let (top, bottom) = img.buffer.split_at_mut((height - 1 - y) * width);
+        top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+    }
+}
+
+// Porter-Duff source-over compositing of two non-premultiplied colors.
+pub fn source_over(src: Rgba, dst: Rgba) -> Rgba {
+    let sa = src.a as u32;
+    let da = dst.a as u32 * (255 - sa) / 255;
+    let a = sa + da;
+    if a == 0 {
+        return Rgba::new(0, 0, 0, 0);
+    }
+    let blend = |s: u8, d: u8| ((s as u32 * sa + d as u32 * da + a / 2) / a) as u8;
+    Rgba::new(
+        blend(src.r, dst.r),
+        blend(src.g, dst.g),
+        blend(src.b, dst.b),
+        a as u8,
+    )
+}
+
+// Bounding box of the pixels changed between two framebuffers.
+#[derive(Debug, Default, PartialEq)]
+pub struct FramebufferDiff {
+    bounds: Option<(usize, usize, usize, usize)>, // inclusive x0, y0, x1, y1
+}
+
+impl FramebufferDiff {
+    fn full(width: usize, height: usize) -> Self {
+        let mut diff = Self::default();
+        if width > 0 && height > 0 {
+            diff.add(0, width - 1, 0);
+            diff.add(0, width - 1, height - 1);
+        }
+        diff
+    }
+
+    fn add(&mut self, x0: usize, x1: usize, y: usize) {
+        self.bounds = Some(match self.bounds {
+            Some((bx0, by0, bx1, by1)) => (bx0.min(x0), by0.min(y), bx1.max(x1), by1.max(y)),
+            None => (x0, y, x1, y),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_none()
+    }
+
+    pub fn rect(&self) -> Option<Rect> {
+        self.bounds
+            .map(|(x0, y0, x1, y1)| Rect::new(x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+    }
+}
+
+impl Deref for Framebuffer {
+    type Target = Image;
+
+    fn deref(&self) -> &Image {
+        &self.img
+    }
+}
+
+impl DerefMut for Framebuffer {
+    fn deref_mut(&mut self) -> &mut Image {
+        &mut self.img
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 3x2 framebuffer with a different red value in each pixel:
+    //   0 1 2
+    //   3 4 5
+    fn numbered() -> Framebuffer {
+        let mut fb = Framebuffer::new(3, 2);
+        for i in 0..6 {
+            fb.set_pixel(i % 3, i / 3, Rgba::new(i as u8, 0, 0, 0xff));
+        }
+        fb
+    }
+
+    fn values(fb: &Framebuffer) -> (usize, usize, Vec<u8>) {
+        (
+            fb.width,
+            fb.height,
+            fb.buffer.iter().map(|px| px.r).collect(),
+        )
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut fb = numbered();
+        fb.rotate_90();
+        assert_eq!(values(&fb), (2, 3, vec![3, 0, 4, 1, 5, 2]));
+
+        let mut fb = numbered();
+        fb.rotate_180();
+        assert_eq!(values(&fb), (3, 2, vec![5, 4, 3, 2, 1, 0]));
+
+        let mut fb = numbered();
+        fb.rotate_270();
+        assert_eq!(values(&fb), (2, 3, vec![2, 5, 1, 4, 0, 3]));
+
+        fb.rotate_90();
+        assert_eq!(values(&fb), values(&numbered()));
+    }
+
+    #[test]
+    fn test_flip() {
+        let mut fb = numbered();
+        fb.flip_horizontal();
+        assert_eq!(values(&fb), (3, 2, vec![2, 1, 0, 5, 4, 3]));
+
+        let mut fb = numbered();
+        fb.flip_vertical();
+        assert_eq!(values(&fb), (3, 2, vec![3, 4, 5, 0, 1, 2]));
+    }
+}
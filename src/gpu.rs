@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+// GPU percentage
+
+#[derive(Debug)]
+pub struct GpuPercentage {
+    pub id: MeterId,
+}
+
+impl GpuPercentage {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for GpuPercentage {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    #[cfg(target_os = "linux")]
+    fn measure(&mut self) -> Res<f32> {
+        // amdgpu and i915 expose the busy percentage in sysfs.
+        if let Ok(s) = fs::read_to_string("/sys/class/drm/card0/device/gpu_busy_percent") {
+            return Ok(s.trim().parse::<f32>()?);
+        }
+
+        // Otherwise ask the nvidia driver.
+        let output = Command::new("nvidia-smi")
+            .args([
+                "--query-gpu=utilization.gpu",
+                "--format=csv,noheader,nounits",
+            ])
+            .output()?;
+        if !output.status.success() {
+            return Err("nvidia-smi failed".into());
+        }
+        let s = String::from_utf8(output.stdout)?;
+        match s.lines().next() {
+            Some(line) => Ok(line.trim().parse::<f32>()?),
+            None => Err("no gpu found".into()),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn measure(&mut self) -> Res<f32> {
+        Err("not supported".into())
+    }
+
+    fn name(&self) -> &str {
+        "GPU usage"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+}
+
+// GPU temperature
+
+#[derive(Debug)]
+pub struct GpuTemperature {
+    pub id: MeterId,
+}
+
+impl GpuTemperature {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for GpuTemperature {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    #[cfg(target_os = "linux")]
+    fn measure(&mut self) -> Res<f32> {
+        for entry in fs::read_dir("/sys/class/hwmon")? {
+            let path = entry?.path();
+            let name = match fs::read_to_string(path.join("name")) {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            if name.contains("amdgpu") || name.contains("nvidia") {
+                let s = fs::read_to_string(path.join("temp1_input"))?;
+                let millidegrees = s.trim().parse::<f32>()?;
+                return Ok(millidegrees / 1000.0);
+            }
+        }
+
+        Err("no gpu sensor found".into())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn measure(&mut self) -> Res<f32> {
+        Err("not supported".into())
+    }
+
+    fn name(&self) -> &str {
+        "GPU temperature"
+    }
+
+    fn unit(&self) -> &str {
+        "°C"
+    }
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::net::SocketAddr;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::mailbox;
+use crate::render::Message;
+use crate::status::Status;
+use crate::Res;
+
+// Time to wait for the renderer to encode a snapshot.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Serve the screen contents and measurements over HTTP:
+//   GET /snapshot  screen as PNG
+//   GET /metrics   measurements as JSON
+pub fn serve(addr: SocketAddr, status: Arc<Status>, screen: mailbox::Sender) -> Res<()> {
+    let server = Server::http(addr)?;
+    tracing::info!("http server listening on {addr}");
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            if let Err(err) = handle(request, &status, &screen) {
+                tracing::warn!("http request error: {err}");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle(request: Request, status: &Status, screen: &mailbox::Sender) -> Res<()> {
+    if *request.method() != Method::Get {
+        request.respond(Response::empty(405))?;
+        return Ok(());
+    }
+
+    let url = request.url().to_string();
+    match url.as_str() {
+        "/snapshot" => match snapshot(screen) {
+            Ok(png) => {
+                let response = Response::from_data(png).with_header(content_type("image/png")?);
+                request.respond(response)?;
+            }
+            Err(err) => {
+                tracing::warn!("cannot get snapshot: {err}");
+                request.respond(Response::empty(500))?;
+            }
+        },
+        "/metrics" => {
+            let response = Response::from_string(status.to_json())
+                .with_header(content_type("application/json")?);
+            request.respond(response)?;
+        }
+        _ => request.respond(Response::empty(404))?,
+    }
+    Ok(())
+}
+
+// Ask the renderer for its current screen contents.
+fn snapshot(screen: &mailbox::Sender) -> Res<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    screen.send(Message::Snapshot(tx))?;
+    let png = rx
+        .recv_timeout(SNAPSHOT_TIMEOUT)
+        .map_err(|_| "renderer didn't send a snapshot")?;
+    Ok(png)
+}
+
+fn content_type(value: &str) -> Res<Header> {
+    let header = Header::from_bytes("Content-Type", value).map_err(|_| "invalid header")?;
+    Ok(header)
+}
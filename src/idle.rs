@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Res;
+
+const INPUT_DIR: &str = "/dev/input";
+
+// Watch keyboard and mouse activity. Send true when no input events were
+// seen for the given timeout, and false when activity resumes.
+pub fn watch(timeout: Duration, tx: mpsc::Sender<bool>) -> Res<()> {
+    let last_activity = Arc::new(Mutex::new(Instant::now()));
+
+    let mut count = 0;
+    for entry in fs::read_dir(INPUT_DIR)?.flatten() {
+        let path = entry.path();
+        let is_event = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("event"));
+        if !is_event {
+            continue;
+        }
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                tracing::debug!("cannot open {}: {err}", path.display());
+                continue;
+            }
+        };
+
+        // Each input device is read in its own thread, blocking until
+        // events arrive.
+        let last_activity = last_activity.clone();
+        thread::spawn(move || {
+            let mut buf = [0; 64];
+            while let Ok(n) = file.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                *last_activity.lock().unwrap() = Instant::now();
+            }
+        });
+        count += 1;
+    }
+
+    if count == 0 {
+        return Err(format!("no readable input devices in {INPUT_DIR}").into());
+    }
+    tracing::info!("watching {count} input devices for activity");
+
+    thread::spawn(move || {
+        let mut idle = false;
+        loop {
+            let elapsed = last_activity.lock().unwrap().elapsed();
+            if (elapsed >= timeout) != idle {
+                idle = !idle;
+                if tx.send(idle).is_err() {
+                    break;
+                }
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::error::Error;
+
+pub mod als;
+pub mod bat;
+pub mod brightness;
+pub mod config;
+pub mod cpu;
+pub mod devices;
+pub mod disk;
+pub mod dryrun;
+pub mod exec;
+pub mod expr;
+pub mod fan;
+pub mod file;
+pub mod fonts;
+pub mod framebuffer;
+pub mod gpu;
+pub mod http;
+pub mod idle;
+pub mod logfile;
+pub mod mailbox;
+pub mod mem;
+pub mod meter;
+pub mod mqtt;
+pub mod net;
+pub mod prometheus;
+pub mod render;
+pub mod scheduler;
+pub mod socket;
+pub mod state;
+pub mod status;
+pub mod sys;
+pub mod themes;
+pub mod udp;
+pub mod watchdog;
+pub mod xdg;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub type Res<T> = Result<T, Box<dyn Error>>;
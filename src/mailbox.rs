@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+
+use arc_swap::ArcSwapOption;
+
+use crate::meter::Measurements;
+use crate::render::Message;
+use crate::Res;
+
+// Channel from the scheduler to a renderer. Measurements are kept in a
+// single slot that always holds the latest values, so a slow renderer
+// never blocks the scheduler; it skips frames instead. Other messages
+// are queued in order.
+pub fn channel() -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::channel();
+    let slot = Arc::new(Slot::default());
+    (
+        Sender {
+            ch: tx,
+            slot: slot.clone(),
+        },
+        Receiver { ch: rx, slot },
+    )
+}
+
+enum Envelope {
+    Wake,
+    Message(Message),
+}
+
+#[derive(Default)]
+struct Slot {
+    latest: ArcSwapOption<Measurements>,
+    dropped: AtomicU64,
+}
+
+#[derive(Clone)]
+pub struct Sender {
+    ch: mpsc::Sender<Envelope>,
+    slot: Arc<Slot>,
+}
+
+impl Sender {
+    pub fn send(&self, msg: Message) -> Res<()> {
+        self.ch
+            .send(Envelope::Message(msg))
+            .map_err(|_| "renderer is gone")?;
+        Ok(())
+    }
+
+    // Replace the pending measurements, if any, with new values.
+    pub fn post(&self, measurements: Measurements) -> Res<()> {
+        if self
+            .slot
+            .latest
+            .swap(Some(Arc::new(measurements)))
+            .is_some()
+        {
+            let dropped = self.slot.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            tracing::debug!("renderer busy, {dropped} frames dropped");
+        }
+        self.ch
+            .send(Envelope::Wake)
+            .map_err(|_| "renderer is gone")?;
+        Ok(())
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.slot.dropped.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Receiver {
+    ch: mpsc::Receiver<Envelope>,
+    slot: Arc<Slot>,
+}
+
+impl Receiver {
+    pub fn recv(&self) -> Result<Message, mpsc::RecvError> {
+        loop {
+            match self.ch.recv()? {
+                Envelope::Message(msg) => return Ok(msg),
+                // Wake-ups for frames already consumed find the slot empty.
+                Envelope::Wake => {
+                    if let Some(latest) = self.slot.latest.swap(None) {
+                        let measurements =
+                            Arc::try_unwrap(latest).unwrap_or_else(|latest| (*latest).clone());
+                        return Ok(Message::Measurements(measurements));
+                    }
+                }
+            }
+        }
+    }
+}
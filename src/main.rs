@@ -1,34 +1,61 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::error::Error;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
 use xxhash_rust::const_xxh3::xxh3_64 as const_xxh3;
 
-use crate::cpu::*;
-use crate::meter::{Measurements, Meter, MeterConfig};
-use crate::render::Renderer;
-use crate::scheduler::{Scheduler, Task};
+use turing_monitor::als::AmbientLight;
+use turing_monitor::bat::*;
+use turing_monitor::brightness::{Brightness, DEFAULT_LEVEL};
+use turing_monitor::config::Config;
+use turing_monitor::cpu::*;
+use turing_monitor::disk::*;
+use turing_monitor::exec::{self, ExecMeter};
+use turing_monitor::expr::Expr;
+use turing_monitor::fan::*;
+use turing_monitor::file::{self, FileMeter};
+use turing_monitor::gpu::*;
+use turing_monitor::logfile::RotatingFile;
+use turing_monitor::mem::*;
+use turing_monitor::meter::{Measurements, Meter, MeterConfig, MeterId};
+use turing_monitor::mqtt::{self, MqttMeter};
+use turing_monitor::net::*;
+use turing_monitor::render::{FrameLimit, Message, Renderer, RendererState, FONTS_DIR};
+use turing_monitor::scheduler::{Scheduler, Stop, Task};
+use turing_monitor::status::Status;
+use turing_monitor::sys::*;
+use turing_monitor::udp::{self, UdpMeter};
+use turing_monitor::xdg::ResourceLocator;
+use turing_monitor::{
+    config, devices, dryrun, http, idle, mailbox, prometheus, socket, state, themes, watchdog, Res,
+};
 
-mod cpu;
-mod meter;
-mod render;
-mod scheduler;
-mod themes;
-
-type Res<T> = Result<T, Box<dyn Error>>;
+// Log output formats. JSON logs have one object per line.
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
 
 #[derive(Parser)]
 #[command(name = "turing-screen")]
 #[command(about = "A lightweight turing smart screen updater")]
 struct Args {
-    /// Set screen brightness in 0-255 range
+    /// Set screen brightness in 0-255 range, or auto to follow the time of day
     #[arg(short, long, value_name = "level")]
-    brightness: Option<i32>,
+    brightness: Option<Brightness>,
 
     /// Screen refresh period in seconds
     #[arg(short, long, value_name = "num", default_value_t = 5)]
@@ -38,18 +65,112 @@ struct Args {
     #[arg(short, long, value_name = "device", default_value_t = String::from("AUTO"))]
     port: String,
 
+    /// Turn screens off after this many seconds without keyboard or mouse activity
+    #[arg(long, value_name = "seconds")]
+    idle_timeout: Option<u64>,
+
+    /// Render frames to PNG files instead of the screen
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Serve the screen contents and measurements over HTTP on this port
+    #[arg(long, value_name = "port")]
+    http_port: Option<u16>,
+
+    /// Address the HTTP server listens on, use 0.0.0.0 for remote access
+    #[arg(long, value_name = "address", default_value_t = IpAddr::from([127, 0, 0, 1]))]
+    http_bind: IpAddr,
+
+    /// Serve measurements for Prometheus scraping on this port
+    #[arg(long, value_name = "port")]
+    prometheus_port: Option<u16>,
+
+    /// Address the Prometheus endpoint listens on, use 0.0.0.0 for remote access
+    #[arg(long, value_name = "address", default_value_t = IpAddr::from([127, 0, 0, 1]))]
+    prometheus_bind: IpAddr,
+
+    /// Answer requests for measurements on this Unix domain socket
+    #[arg(long, value_name = "path")]
+    socket: Option<PathBuf>,
+
+    /// Render a single frame and exit
+    #[arg(long)]
+    once: bool,
+
+    /// Exit after rendering this many frames
+    #[arg(long, value_name = "n", value_parser = clap::value_parser!(u64).range(1..))]
+    frame_limit: Option<u64>,
+
+    /// Multiply widget positions and sizes by this factor
+    #[arg(long, value_name = "factor", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    scale: u32,
+
     /// Enable debug messages
     #[arg(short, long)]
     debug: bool,
 
-    #[arg(value_name = "theme_name")]
-    theme: String,
+    /// Format of log messages
+    #[arg(long, value_name = "format", value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Write log messages to a file instead of the standard error
+    #[arg(long, value_name = "path")]
+    log_file: Option<PathBuf>,
+
+    /// Rotate the log file when it grows over this size
+    #[arg(long, value_name = "MB", default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    log_max_size: u64,
+
+    /// Number of rotated log files to keep
+    #[arg(long, value_name = "n", default_value_t = 5)]
+    log_keep: usize,
+
+    /// Read default settings from a configuration file
+    #[arg(short, long, value_name = "path")]
+    config: Option<PathBuf>,
+
+    /// Check a theme file for errors and exit
+    #[arg(long, value_name = "path")]
+    validate_theme: Option<PathBuf>,
+
+    /// Directory to search for themes
+    #[arg(long, value_name = "path")]
+    theme_dir: Option<PathBuf>,
+
+    /// Directory to search for fonts
+    #[arg(long, value_name = "path")]
+    font_dir: Option<PathBuf>,
+
+    /// List connected screens and exit
+    #[arg(long)]
+    list_devices: bool,
+
+    /// List serial ports of connected screens with their manufacturer and exit
+    #[arg(long)]
+    port_list: bool,
+
+    /// List available themes and exit
+    #[arg(long)]
+    list_themes: bool,
+
+    /// Themes to use, one for each screen
+    #[arg(
+        value_name = "theme_name",
+        required_unless_present_any = ["validate_theme", "list_themes", "list_devices", "port_list"]
+    )]
+    theme: Vec<String>,
 }
 
 fn main() {
-    let args = Args::parse();
+    let (args, config) = match parse_args() {
+        Ok(res) => res,
+        Err(err) => {
+            eprintln!("error: {err}");
+            process::exit(1);
+        }
+    };
 
-    match run(args) {
+    match run(args, config) {
         Ok(_) => (),
         Err(err) => {
             eprintln!("error: {err}");
@@ -58,70 +179,550 @@ fn main() {
     }
 }
 
-fn run(args: Args) -> Result<(), Box<dyn Error>> {
-    let level = if args.debug {
-        log::Level::Debug
+// Parse the command line, using values from the configuration file
+// as defaults.
+fn parse_args() -> Res<(Args, Config)> {
+    let mut cmd = Args::command();
+    let cfg = match config::path_from_args() {
+        Some(path) => config::load(&path)?,
+        None => Config::default(),
+    };
+    if let Some(val) = &cfg.brightness {
+        cmd = cmd.mut_arg("brightness", |a| a.default_value(val.to_string()));
+    }
+    if let Some(val) = cfg.refresh {
+        cmd = cmd.mut_arg("refresh", |a| a.default_value(val.to_string()));
+    }
+    if let Some(val) = cfg.port.clone() {
+        cmd = cmd.mut_arg("port", |a| a.default_value(val));
+    }
+    if let Some(val) = cfg.theme.clone() {
+        cmd = cmd.mut_arg("theme", |a| a.default_value(val));
+    }
+    if let Some(val) = cfg.themes.clone() {
+        cmd = cmd.mut_arg("theme", |a| a.default_values(val));
+    }
+
+    let matches = cmd.get_matches();
+    Ok((Args::from_arg_matches(&matches)?, cfg))
+}
+
+fn run(args: Args, config: Config) -> Result<(), Box<dyn Error>> {
+    // RUST_LOG selects what to log unless debug messages are requested.
+    // Closing spans are logged with their duration.
+    let filter = if args.debug {
+        EnvFilter::new("debug")
     } else {
-        log::Level::Info
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+    };
+    let writer = match &args.log_file {
+        Some(path) => {
+            let max_size = args.log_max_size * 1024 * 1024;
+            let file = RotatingFile::open(path, max_size, args.log_keep)?;
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+        None => BoxMakeWriter::new(io::stderr),
     };
-    simple_logger::init_with_level(level)?;
+    let logger = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(args.log_file.is_none())
+        .with_writer(writer);
+    match args.log_format {
+        LogFormat::Text => logger.try_init()?,
+        LogFormat::Json => logger.json().try_init()?,
+    }
 
-    let refresh_period = Duration::from_secs(args.refresh);
-    let theme_name = args.theme;
-    let theme = themes::load(&theme_name)?;
+    // Use the directories given in the command line, or search the
+    // standard locations.
+    let theme_dirs = match &args.theme_dir {
+        Some(dir) => ResourceLocator::with_dir(dir),
+        None => ResourceLocator::new("themes", Path::new(themes::THEMES_DIR)),
+    };
+    let font_dirs = match &args.font_dir {
+        Some(dir) => ResourceLocator::with_dir(dir),
+        None => ResourceLocator::new("fonts", Path::new(FONTS_DIR)),
+    };
 
-    log::info!("using theme: {theme_name}");
+    if let Some(path) = &args.validate_theme {
+        return validate_theme(path, &font_dirs);
+    }
 
-    let mut measurements = Measurements::new();
-    let configs = themes::get_meter_list(&theme);
-    for cfg in &configs {
-        measurements.insert(cfg.id, 0.0);
+    if args.list_devices {
+        for dev in devices::enumerate()? {
+            let serial_number = dev.serial_number.as_deref().unwrap_or("-");
+            println!("{}\t{serial_number}", dev.port);
+        }
+        return Ok(());
     }
 
-    // Image rendering thread: prepare framebuffer and communicate
-    // with device.
-    let (tx, rx) = mpsc::sync_channel(1);
-    let renderer_configs = configs.clone();
-    thread::spawn(move || {
-        let mut renderer = match Renderer::new(rx, renderer_configs) {
-            Ok(r) => r,
-            Err(err) => {
-                log::error!("error: {err}");
-                return;
+    if args.port_list {
+        for dev in devices::enumerate()? {
+            let manufacturer = dev.manufacturer.as_deref().unwrap_or("unknown");
+            println!("{}\t{manufacturer}", dev.port);
+        }
+        return Ok(());
+    }
+
+    if args.list_themes {
+        for name in themes::list(&theme_dirs)? {
+            println!("{name}");
+        }
+        return Ok(());
+    }
+
+    let refresh_period = Duration::from_secs(args.refresh);
+    let theme_names = args.theme;
+    let theme_list = load_themes(&theme_dirs, &theme_names)?;
+
+    let configs = merge_meter_lists(&theme_list);
+    let mut measurements = new_measurements(&configs);
+
+    // Follow the ambient light sensor, if present, or the time of day
+    // if brightness is set to auto.
+    let brightness = args.brightness.unwrap_or(Brightness::Level(DEFAULT_LEVEL));
+    let schedule = config.brightness_schedule.unwrap_or_default();
+    let ambient_light = match brightness {
+        Brightness::Auto => AmbientLight::find(config.ambient_light.unwrap_or_default()),
+        Brightness::Level(_) => None,
+    };
+    let mut initial_brightness = match (brightness, &ambient_light) {
+        (Brightness::Level(level), _) => level,
+        (Brightness::Auto, Some(sensor)) => sensor.level().unwrap_or_else(|err| {
+            tracing::warn!("cannot read ambient light sensor: {err}");
+            schedule.current_level()
+        }),
+        (Brightness::Auto, None) => schedule.current_level(),
+    };
+
+    // Restore the last brightness level if the previous run crashed,
+    // unless a level was explicitly set. Dry runs don't use the screen,
+    // so they can run next to the daemon and leave its state alone.
+    let (_pid_file, crashed) = if args.dry_run {
+        (None, false)
+    } else {
+        let (pid_file, crashed) = state::PidFile::create()?;
+        (Some(pid_file), crashed)
+    };
+    if crashed && !matches!(args.brightness, Some(Brightness::Level(_))) {
+        match state::load() {
+            Ok(state::State {
+                brightness: Some(level),
+            }) => {
+                tracing::info!("restoring brightness {level} after unclean exit");
+                initial_brightness = level;
             }
-        };
-        renderer.start();
-    });
+            Ok(_) => (),
+            Err(err) => tracing::warn!("cannot restore state: {err}"),
+        }
+    }
+    if !args.dry_run {
+        state::save_brightness(initial_brightness);
+    }
+
+    // Reload theme on SIGHUP.
+    let reload = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, reload.clone())?;
+
+    // Shut down cleanly on Ctrl-C or SIGTERM.
+    let quit = Arc::new(AtomicBool::new(false));
+    let quit_flag = quit.clone();
+    ctrlc::set_handler(move || quit_flag.store(true, Ordering::Relaxed))?;
 
     // Main loop: collect pc stats.
-    let mut scheduler = Scheduler::new(tx, refresh_period);
+    let (senders, receivers): (Vec<_>, Vec<_>) =
+        theme_list.iter().map(|_| mailbox::channel()).unzip();
+    let renderer_tx = senders.clone();
+    let frame_limit = args
+        .frame_limit
+        .map(|frames| Arc::new(FrameLimit::new(frames, theme_list.len(), quit.clone())));
+    let mut scheduler = Scheduler::new(senders, refresh_period, reload, quit.clone());
+    scheduler.set_save_state(!args.dry_run);
+    if brightness == Brightness::Auto {
+        scheduler.set_brightness_schedule(schedule);
+        if let Some(sensor) = ambient_light {
+            scheduler.set_ambient_light(sensor, initial_brightness);
+        }
+    }
+    if let Some(timeout) = args.idle_timeout {
+        let (tx, rx) = mpsc::channel();
+        match idle::watch(Duration::from_secs(timeout), tx) {
+            Ok(_) => scheduler.set_idle_watch(rx),
+            Err(err) => tracing::warn!("cannot detect idle state: {err}"),
+        }
+    }
+    let status = Arc::new(Status::default());
+    status.set_names(&configs);
+    scheduler.set_status(status.clone());
     register_meters(&mut scheduler, configs);
-    scheduler.start(measurements);
+    status.set_descriptions(scheduler.descriptions());
+    let ranges = scheduler.ranges();
+
+    // Image rendering threads, one per screen: prepare framebuffer
+    // and communicate with device.
+    let mut renderers = Vec::new();
+    let screen_count = theme_list.len();
+    for (i, (theme, rx)) in theme_list.iter().zip(receivers).enumerate() {
+        let state = Arc::new(Mutex::new(RendererState {
+            theme: theme.clone(),
+            configs: themes::get_meter_list(theme),
+            ranges: ranges.clone(),
+            brightness: initial_brightness,
+        }));
+        let font_dirs = font_dirs.clone();
+        let port = match theme.serial_number() {
+            _ if args.dry_run => String::new(),
+            Some(serial_number) => devices::find_port(serial_number)?,
+            None => theme.port().unwrap_or(&args.port).to_string(),
+        };
+        let frame_prefix = match screen_count {
+            1 => "frame".to_string(),
+            _ => format!("screen{i}_frame"),
+        };
+        let dry_run = args.dry_run;
+        let scale = args.scale;
+        let frame_limit = frame_limit.clone();
+        let quit = quit.clone();
+        let handle = thread::spawn(move || {
+            // Screens and renderers are created again if the watchdog
+            // restarts them, with the latest theme and brightness.
+            let run = || -> Res<()> {
+                let RendererState {
+                    theme,
+                    configs,
+                    ranges,
+                    brightness,
+                } = state.lock().unwrap().clone();
+                let (scr, capture) = if dry_run {
+                    let (width, height) = match (theme.screen_size(), theme.rotation()) {
+                        ((width, height), 90 | 270) => (height, width),
+                        (size, _) => size,
+                    };
+                    let (width, height) = ((width * scale) as usize, (height * scale) as usize);
+                    let (scr, capture) = dryrun::new(width, height, &frame_prefix);
+                    (scr, Some(capture))
+                } else {
+                    (turing_screen::new(&port)?, None)
+                };
+                let mut renderer = Renderer::new(
+                    &theme,
+                    configs,
+                    ranges,
+                    font_dirs.clone(),
+                    scr,
+                    brightness,
+                    scale,
+                )?;
+                renderer.set_state(state.clone());
+                if let Some(capture) = capture {
+                    renderer.set_frame_capture(capture);
+                }
+                if let Some(limit) = &frame_limit {
+                    renderer.set_frame_limit(limit.clone());
+                }
+                if !dry_run {
+                    renderer.set_reconnect(port.clone(), quit.clone());
+                }
+                renderer.set_refresh_period(refresh_period);
+                renderer.start(&rx)
+            };
+            watchdog::supervise(&format!("screen {i}"), &quit, run);
+        });
+        renderers.push(handle);
+    }
+
+    // Snapshots show the first screen.
+    if let Some(port) = args.http_port {
+        http::serve(
+            (args.http_bind, port).into(),
+            status.clone(),
+            renderer_tx[0].clone(),
+        )?;
+    }
+    if let Some(path) = &args.socket {
+        socket::serve(path, status.clone())?;
+    }
+    if let Some(port) = args.prometheus_port {
+        prometheus::serve((args.prometheus_bind, port).into(), status.clone())?;
+    }
+
+    if args.once {
+        scheduler.run_once(&mut measurements);
+    } else {
+        loop {
+            if scheduler.start(measurements.clone()) == Stop::Quit {
+                break;
+            }
+
+            // The scheduler stops when a reload is requested, and starts
+            // again once meters and widgets are rebuilt.
+            let theme_list = match load_themes(&theme_dirs, &theme_names) {
+                Ok(list) => list,
+                Err(err) => {
+                    tracing::error!("cannot reload theme: {err}");
+                    continue;
+                }
+            };
+            let configs = merge_meter_lists(&theme_list);
+            measurements = new_measurements(&configs);
+            status.set_names(&configs);
+            scheduler.clear_tasks();
+            register_meters(&mut scheduler, configs);
+            status.set_descriptions(scheduler.descriptions());
+            let ranges = scheduler.ranges();
+            for (tx, theme) in renderer_tx.iter().zip(theme_list) {
+                let configs = themes::get_meter_list(&theme);
+                tx.send(Message::Reload(theme, configs, ranges.clone()))?;
+            }
+        }
+    }
+
+    // Tell renderers to turn off their screens and wait for them.
+    tracing::info!("shutting down");
+    for tx in &renderer_tx {
+        // The renderer may have already exited.
+        let _ = tx.send(Message::Quit);
+    }
+    for handle in renderers {
+        let _ = handle.join();
+    }
+    for (i, tx) in renderer_tx.iter().enumerate() {
+        tracing::debug!("screen {i}: {} frames dropped", tx.dropped());
+    }
+    if let Some(path) = &args.socket {
+        let _ = std::fs::remove_file(path);
+    }
+
+    Ok(())
+}
+
+fn load_themes(theme_dirs: &ResourceLocator, names: &[String]) -> Res<Vec<themes::Theme>> {
+    let mut res = Vec::new();
+    for name in names {
+        tracing::info!("using theme: {name}");
+        res.push(themes::load(theme_dirs, name)?);
+    }
+    Ok(res)
+}
+
+// Get the meters used by all themes, measuring shared meters only once.
+fn merge_meter_lists(theme_list: &[themes::Theme]) -> Vec<MeterConfig> {
+    let mut res = Vec::<MeterConfig>::new();
+    for theme in theme_list {
+        for cfg in themes::get_meter_list(theme) {
+            if !res.iter().any(|c| c.id == cfg.id) {
+                res.push(cfg);
+            }
+        }
+    }
+    res
+}
+
+fn validate_theme(path: &Path, fonts: &ResourceLocator) -> Res<()> {
+    let theme = themes::load_file(path)?;
+    let (width, height) = theme.screen_size();
+    let configs = themes::get_meter_list(&theme);
+
+    println!("theme: {}", path.display());
+    println!("screen size: {width}x{height}");
+
+    let mut problems = Vec::<String>::new();
+    let background = theme.background_path();
+    if !background.exists() {
+        problems.push(format!("background not found: {}", background.display()));
+    }
+
+    for text in theme.static_text() {
+        let font = text.font_file();
+        if fonts.locate(&font).is_none() {
+            problems.push(format!("static text: font not found: {font}"));
+        }
+    }
+
+    for cfg in &configs {
+        let mut errors = Vec::new();
+        if !known_meter(cfg.id) && !sourced_meter(&cfg.name) {
+            errors.push("unknown meter".to_string());
+        }
+        if let Some(text) = &cfg.layout.text {
+            let font = text.font_file();
+            if fonts.locate(&font).is_none() {
+                errors.push(format!("font not found: {font}"));
+            }
+        }
+        if let Some(transform) = &cfg.transform {
+            if let Err(err) = Expr::parse(transform) {
+                errors.push(format!("invalid transform: {err}"));
+            }
+        }
+        errors.extend(cfg.layout.check_bounds(width, height));
+
+        if errors.is_empty() {
+            println!("  {}: ok", cfg.name);
+        }
+        for err in errors {
+            println!("  {}: {err}", cfg.name);
+            problems.push(format!("{}: {err}", cfg.name));
+        }
+    }
+
+    println!(
+        "{} meters checked, {} problems found",
+        configs.len(),
+        problems.len()
+    );
+    if !problems.is_empty() {
+        return Err("invalid theme".into());
+    }
 
     Ok(())
 }
 
+fn new_measurements(configs: &[MeterConfig]) -> Measurements {
+    let mut measurements = Measurements::new();
+    for cfg in configs {
+        measurements.insert(cfg.id, 0.0);
+    }
+    measurements
+}
+
 fn register_meters(scheduler: &mut Scheduler, configs: Vec<MeterConfig>) {
     for cfg in configs {
-        match create_meter(cfg.id) {
-            Ok(m) => {
-                let interval = Duration::from_secs(cfg.interval.into());
-                scheduler.register_task(Task::new(m, interval));
-            }
+        match create_task(&cfg) {
+            Ok(task) => scheduler.register_task(task),
             Err(err) => {
-                log::warn!("cannot register {}: {}", cfg.id, err);
+                tracing::warn!("cannot register {}: {}", cfg.name, err);
             }
         }
     }
 }
 
-const CPU_PERCENTAGE: u64 = const_xxh3(b"CPU:PERCENTAGE");
-const CPU_TEMPERATURE: u64 = const_xxh3(b"CPU:TEMPERATURE");
+fn create_task(cfg: &MeterConfig) -> Res<Task> {
+    let m = create_meter(cfg)?;
+    let interval = Duration::from_secs(cfg.interval.into());
+    let mut task = Task::new(m, interval)
+        .with_smoothing(cfg.smoothing)
+        .with_limits(cfg.min, cfg.max)
+        .with_jitter(Duration::from_secs_f32(cfg.jitter.max(0.0)))
+        .with_priority(cfg.priority);
+    if let Some(transform) = &cfg.transform {
+        task = task.with_transform(Expr::parse(transform)?);
+    }
+    Ok(task)
+}
+
+const CPU_PERCENTAGE: MeterId = MeterId(const_xxh3(b"CPU:PERCENTAGE"));
+const CPU_TEMPERATURE: MeterId = MeterId(const_xxh3(b"CPU:TEMPERATURE"));
+const CPU_FREQ_MHZ: MeterId = MeterId(const_xxh3(b"CPU:FREQ_MHZ"));
+const GPU_PERCENTAGE: MeterId = MeterId(const_xxh3(b"GPU:PERCENTAGE"));
+const GPU_TEMPERATURE: MeterId = MeterId(const_xxh3(b"GPU:TEMPERATURE"));
+const MEM_USED_PERCENT: MeterId = MeterId(const_xxh3(b"MEM:USED_PERCENT"));
+const MEM_USED_BYTES: MeterId = MeterId(const_xxh3(b"MEM:USED_BYTES"));
+const SWAP_USED_PERCENT: MeterId = MeterId(const_xxh3(b"SWAP:USED_PERCENT"));
+const DISK_READ_MBPS: MeterId = MeterId(const_xxh3(b"DISK:READ_MBPS"));
+const DISK_WRITE_MBPS: MeterId = MeterId(const_xxh3(b"DISK:WRITE_MBPS"));
+const DISK_USED_PERCENT: MeterId = MeterId(const_xxh3(b"DISK:USED_PERCENT"));
+const NET_RX_MBPS: MeterId = MeterId(const_xxh3(b"NET:RX_MBPS"));
+const NET_TX_MBPS: MeterId = MeterId(const_xxh3(b"NET:TX_MBPS"));
+const BAT_CHARGE_PERCENT: MeterId = MeterId(const_xxh3(b"BAT:CHARGE_PERCENT"));
+const BAT_IS_CHARGING: MeterId = MeterId(const_xxh3(b"BAT:IS_CHARGING"));
+const FAN_RPM: MeterId = MeterId(const_xxh3(b"FAN:RPM"));
+const SYS_LOAD_1: MeterId = MeterId(const_xxh3(b"SYS:LOAD_1"));
+const SYS_LOAD_5: MeterId = MeterId(const_xxh3(b"SYS:LOAD_5"));
+const SYS_LOAD_15: MeterId = MeterId(const_xxh3(b"SYS:LOAD_15"));
+const SYS_UPTIME_HOURS: MeterId = MeterId(const_xxh3(b"SYS:UPTIME_HOURS"));
+const SYS_PROCESS_COUNT: MeterId = MeterId(const_xxh3(b"SYS:PROCESS_COUNT"));
+
+// All meters known to create_meter, except indexed per-core meters.
+const METERS: &[MeterId] = &[
+    CPU_PERCENTAGE,
+    CPU_TEMPERATURE,
+    CPU_FREQ_MHZ,
+    GPU_PERCENTAGE,
+    GPU_TEMPERATURE,
+    MEM_USED_PERCENT,
+    MEM_USED_BYTES,
+    SWAP_USED_PERCENT,
+    DISK_READ_MBPS,
+    DISK_WRITE_MBPS,
+    DISK_USED_PERCENT,
+    NET_RX_MBPS,
+    NET_TX_MBPS,
+    BAT_CHARGE_PERCENT,
+    BAT_IS_CHARGING,
+    FAN_RPM,
+    SYS_LOAD_1,
+    SYS_LOAD_5,
+    SYS_LOAD_15,
+    SYS_UPTIME_HOURS,
+    SYS_PROCESS_COUNT,
+];
+
+const MAX_CORES: usize = 32;
+
+// Find the core index of an indexed meter such as CPU:CORE_PCT_<N>.
+fn core_index(prefix: &str, id: MeterId) -> Option<usize> {
+    (0..MAX_CORES).find(|i| MeterId::from_name(&format!("{prefix}_{i}")) == id)
+}
+
+fn known_meter(id: MeterId) -> bool {
+    METERS.contains(&id)
+        || core_index("CPU:CORE_PCT", id).is_some()
+        || core_index("CPU:CORE_TEMP", id).is_some()
+}
+
+// Meters named after their source, created from the name prefix.
+fn sourced_meter(name: &str) -> bool {
+    [exec::PREFIX, file::PREFIX, udp::PREFIX, mqtt::PREFIX]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+fn create_meter(cfg: &MeterConfig) -> Result<Box<dyn Meter>, Box<dyn Error>> {
+    let id = cfg.id;
+    let source = cfg.source.as_deref();
+
+    if cfg.name.starts_with(exec::PREFIX) {
+        return Ok(Box::new(ExecMeter::new(id, source)?));
+    }
+    if cfg.name.starts_with(file::PREFIX) {
+        return Ok(Box::new(FileMeter::new(id, source)?));
+    }
+    if cfg.name.starts_with(udp::PREFIX) {
+        return Ok(Box::new(UdpMeter::new(id, source)?));
+    }
+    if cfg.name.starts_with(mqtt::PREFIX) {
+        return Ok(Box::new(MqttMeter::new(id, source)?));
+    }
+    if let Some(core) = core_index("CPU:CORE_PCT", id) {
+        return Ok(Box::new(CpuCorePercentage::new(id, core)?));
+    }
+    if let Some(core) = core_index("CPU:CORE_TEMP", id) {
+        return Ok(Box::new(CpuCoreTemperature::new(id, core)?));
+    }
 
-fn create_meter(id: u64) -> Result<Box<dyn Meter>, Box<dyn Error>> {
     let m: Box<dyn Meter> = match id {
         CPU_PERCENTAGE => Box::new(CpuPercentage::new(id)?),
         CPU_TEMPERATURE => Box::new(CpuTemperature::new(id)?),
+        CPU_FREQ_MHZ => Box::new(CpuFreqMhz::new(id, source)?),
+        GPU_PERCENTAGE => Box::new(GpuPercentage::new(id)?),
+        GPU_TEMPERATURE => Box::new(GpuTemperature::new(id)?),
+        MEM_USED_PERCENT => Box::new(MemUsedPercent::new(id)?),
+        MEM_USED_BYTES => Box::new(MemUsedBytes::new(id)?),
+        SWAP_USED_PERCENT => Box::new(SwapUsedPercent::new(id)?),
+        DISK_READ_MBPS => Box::new(DiskReadMbps::new(id, source)?),
+        DISK_WRITE_MBPS => Box::new(DiskWriteMbps::new(id, source)?),
+        DISK_USED_PERCENT => Box::new(DiskUsedPercent::new(id, source)?),
+        NET_RX_MBPS => Box::new(NetRxMbps::new(id, source)?),
+        NET_TX_MBPS => Box::new(NetTxMbps::new(id, source)?),
+        BAT_CHARGE_PERCENT => Box::new(BatChargePercent::new(id)?),
+        BAT_IS_CHARGING => Box::new(BatIsCharging::new(id)?),
+        FAN_RPM => Box::new(FanRpm::new(id, source)?),
+        SYS_LOAD_1 => Box::new(SysLoad1::new(id)?),
+        SYS_LOAD_5 => Box::new(SysLoad5::new(id)?),
+        SYS_LOAD_15 => Box::new(SysLoad15::new(id)?),
+        SYS_UPTIME_HOURS => Box::new(SysUptimeHours::new(id)?),
+        SYS_PROCESS_COUNT => Box::new(SysProcessCount::new(id)?),
         _ => return Err("invalid meter".into()),
     };
 
@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::fs;
+
+#[cfg(not(target_os = "linux"))]
+use psutil::memory;
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+// Parse /proc/meminfo into a map of field name to value in bytes.
+#[cfg(target_os = "linux")]
+fn meminfo() -> Res<HashMap<String, u64>> {
+    let s = fs::read_to_string("/proc/meminfo")?;
+    let mut map = HashMap::new();
+    for line in s.lines() {
+        let mut parts = line.split_whitespace();
+        if let (Some(key), Some(val)) = (parts.next(), parts.next()) {
+            let mut val = val.parse::<u64>()?;
+            if parts.next() == Some("kB") {
+                val *= 1024;
+            }
+            map.insert(key.trim_end_matches(':').to_string(), val);
+        }
+    }
+    Ok(map)
+}
+
+#[cfg(target_os = "linux")]
+fn meminfo_field(info: &HashMap<String, u64>, key: &str) -> Res<u64> {
+    match info.get(key) {
+        Some(val) => Ok(*val),
+        None => Err(format!("{key} not found in /proc/meminfo").into()),
+    }
+}
+
+// Memory used percentage
+
+#[derive(Debug)]
+pub struct MemUsedPercent {
+    pub id: MeterId,
+}
+
+impl MemUsedPercent {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for MemUsedPercent {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    #[cfg(target_os = "linux")]
+    fn measure(&mut self) -> Res<f32> {
+        let info = meminfo()?;
+        let total = meminfo_field(&info, "MemTotal")?;
+        let available = meminfo_field(&info, "MemAvailable")?;
+        if total == 0 {
+            return Ok(0.0);
+        }
+        Ok((total - available) as f32 * 100.0 / total as f32)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn measure(&mut self) -> Res<f32> {
+        let vm = memory::virtual_memory()?;
+        Ok(vm.percent())
+    }
+
+    fn name(&self) -> &str {
+        "Memory used"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+}
+
+// Memory used bytes
+
+#[derive(Debug)]
+pub struct MemUsedBytes {
+    pub id: MeterId,
+    total: f32,
+}
+
+impl MemUsedBytes {
+    #[cfg(target_os = "linux")]
+    pub fn new(id: MeterId) -> Res<Self> {
+        let total = meminfo_field(&meminfo()?, "MemTotal")? as f32;
+        Ok(Self { id, total })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(id: MeterId) -> Res<Self> {
+        let total = memory::virtual_memory()?.total() as f32;
+        Ok(Self { id, total })
+    }
+}
+
+impl Meter for MemUsedBytes {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    #[cfg(target_os = "linux")]
+    fn measure(&mut self) -> Res<f32> {
+        let info = meminfo()?;
+        let total = meminfo_field(&info, "MemTotal")?;
+        let available = meminfo_field(&info, "MemAvailable")?;
+        Ok((total - available) as f32)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn measure(&mut self) -> Res<f32> {
+        let vm = memory::virtual_memory()?;
+        Ok(vm.used() as f32)
+    }
+
+    fn name(&self) -> &str {
+        "Memory used"
+    }
+
+    fn unit(&self) -> &str {
+        "B"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, self.total)
+    }
+}
+
+// Swap used percentage
+
+#[derive(Debug)]
+pub struct SwapUsedPercent {
+    pub id: MeterId,
+}
+
+impl SwapUsedPercent {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for SwapUsedPercent {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    #[cfg(target_os = "linux")]
+    fn measure(&mut self) -> Res<f32> {
+        let info = meminfo()?;
+        let total = meminfo_field(&info, "SwapTotal")?;
+        let free = meminfo_field(&info, "SwapFree")?;
+        if total == 0 {
+            // No swap configured.
+            return Ok(0.0);
+        }
+        Ok((total - free) as f32 * 100.0 / total as f32)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn measure(&mut self) -> Res<f32> {
+        let swap = memory::swap_memory()?;
+        if swap.total() == 0 {
+            return Ok(0.0);
+        }
+        Ok(swap.percent())
+    }
+
+    fn name(&self) -> &str {
+        "Swap used"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+}
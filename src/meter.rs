@@ -1,20 +1,80 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::collections::HashMap;
+use std::fmt;
+
+use xxhash_rust::xxh3::xxh3_64;
 
 use crate::themes::DeviceMeter;
 use crate::Res;
 
+// Meter identifier, the xxh3 hash of the meter name (e.g. "CPU:PERCENTAGE").
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeterId(pub u64);
+
+impl MeterId {
+    pub fn from_name(name: &str) -> MeterId {
+        MeterId(xxh3_64(name.as_bytes()))
+    }
+}
+
+// Name of a meter identified by its source, the family prefix followed by
+// the source hash, such as CMD:1f2e3d4c5b6a7988.
+pub fn source_meter_name(prefix: &str, source: &str) -> String {
+    format!("{prefix}{:016x}", xxh3_64(source.as_bytes()))
+}
+
+impl fmt::Display for MeterId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
 pub trait Meter {
-    fn id(&self) -> u64;
+    fn id(&self) -> MeterId;
     fn measure(&mut self) -> Res<f32>;
+
+    // Meters producing several values at once, such as per-core meters,
+    // can update all their entries in a single pass.
+    fn measure_many(&mut self) -> Res<Vec<(MeterId, f32)>> {
+        Ok(vec![(self.id(), self.measure()?)])
+    }
+
+    // Human readable description, such as "CPU frequency".
+    fn name(&self) -> &str {
+        ""
+    }
+
+    // Unit of the measured values, such as "MHz".
+    fn unit(&self) -> &str {
+        ""
+    }
+
+    // Expected minimum and maximum values, used to scale widgets unless
+    // the theme sets MIN and MAX. Percentages by default.
+    fn range(&self) -> (f32, f32) {
+        (0.0, 100.0)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct MeterConfig {
-    pub id: u64,
+    pub id: MeterId,
+    pub name: String,
     pub interval: u32,
+    pub source: Option<String>,
+    pub smoothing: f32, // time constant in seconds, 0 for no smoothing
+    pub min: f32,
+    pub max: f32,
+    pub transform: Option<String>,
+    pub jitter: f32, // seconds
+    pub priority: u8,
     pub layout: DeviceMeter,
 }
 
-pub type Measurements = HashMap<u64, f32>;
+pub type Measurements = HashMap<MeterId, f32>;
+
+pub type Ranges = HashMap<MeterId, (f32, f32)>;
+
+// Human readable description and unit of each meter.
+pub type Descriptions = HashMap<MeterId, (String, String)>;
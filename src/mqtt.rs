@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+// Name prefix of meters that subscribe to an MQTT topic.
+pub const PREFIX: &str = "MQTT:";
+
+const DEFAULT_PORT: u16 = 1883;
+
+// Time to wait before polling the broker again after an error.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Latest value published to an MQTT topic as text, such as "42.5".
+pub struct MqttMeter {
+    pub id: MeterId,
+    client: Client,
+    last: Arc<Mutex<Option<f32>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MqttMeter {
+    // The source is the broker and topic, as in host[:port]/topic.
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        let source = source.ok_or("MQTT meter requires a source")?;
+        let (host, port, topic) = parse_source(source)?;
+
+        let client_id = format!("turing-monitor-{}-{id}", process::id());
+        let mut options = MqttOptions::new(client_id, host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        let (client, mut connection) = Client::new(options, 10);
+
+        let last = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (value, stopped, subscriber) = (last.clone(), stop.clone(), client.clone());
+        thread::spawn(move || {
+            for notification in connection.iter() {
+                match notification {
+                    // Subscribe again after every reconnection.
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        if let Err(err) = subscriber.try_subscribe(&topic, QoS::AtMostOnce) {
+                            tracing::warn!("cannot subscribe to {topic}: {err}");
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(msg))) => match parse(&msg.payload) {
+                        Some(val) => *value.lock().unwrap() = Some(val),
+                        None => tracing::debug!("invalid value in {}", msg.topic),
+                    },
+                    Ok(_) => (),
+                    Err(_) if stopped.load(Ordering::Relaxed) => break,
+                    Err(err) => {
+                        tracing::warn!("mqtt connection error: {err}");
+                        thread::sleep(RETRY_DELAY);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            id,
+            client,
+            last,
+            stop,
+        })
+    }
+}
+
+impl Drop for MqttMeter {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.client.try_disconnect();
+    }
+}
+
+impl Meter for MqttMeter {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let last = *self.last.lock().unwrap();
+        last.ok_or_else(|| "no value received".into())
+    }
+}
+
+// Split host[:port]/topic into its parts.
+fn parse_source(source: &str) -> Res<(String, u16, String)> {
+    let (addr, topic) = source
+        .trim()
+        .split_once('/')
+        .ok_or_else(|| format!("missing topic in {source}"))?;
+    let (host, port) = match addr.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse()?),
+        None => (addr, DEFAULT_PORT),
+    };
+    if host.is_empty() || topic.is_empty() {
+        return Err(format!("invalid MQTT source {source}").into());
+    }
+    Ok((host.to_string(), port, topic.to_string()))
+}
+
+fn parse(data: &[u8]) -> Option<f32> {
+    std::str::from_utf8(data).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            parse_source("broker:1884/home/temp").unwrap(),
+            ("broker".to_string(), 1884, "home/temp".to_string())
+        );
+        assert_eq!(
+            parse_source("broker/temp").unwrap(),
+            ("broker".to_string(), DEFAULT_PORT, "temp".to_string())
+        );
+        assert!(parse_source("broker").is_err());
+        assert!(parse_source("broker:x/temp").is_err());
+        assert!(parse_source("/temp").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::time::Instant;
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+// Top of the throughput scale when the link speed is unknown, 1 Gbit/s.
+const DEFAULT_MAX_MBPS: f32 = 125.0;
+
+// Find the first network interface that isn't loopback.
+fn default_interface() -> Res<String> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(SYS_CLASS_NET)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if name != "lo" {
+            names.push(name);
+        }
+    }
+    names.sort();
+    match names.into_iter().next() {
+        Some(name) => Ok(name),
+        None => Err("no network interface found".into()),
+    }
+}
+
+// Throughput computed from the difference between two samples of an
+// interface statistics counter.
+#[derive(Debug)]
+struct Throughput {
+    path: String,
+    last: Option<(Instant, u64)>,
+    max_mbps: f32,
+}
+
+impl Throughput {
+    fn new(source: Option<&str>, counter: &str) -> Res<Self> {
+        let iface = match source {
+            Some(name) => name.to_string(),
+            None => default_interface()?,
+        };
+        // The link speed is given in Mbit/s, and is -1 or missing for
+        // some interfaces.
+        let max_mbps = fs::read_to_string(format!("{SYS_CLASS_NET}/{iface}/speed"))
+            .ok()
+            .and_then(|s| s.trim().parse::<f32>().ok())
+            .filter(|&speed| speed > 0.0)
+            .map_or(DEFAULT_MAX_MBPS, |speed| speed / 8.0);
+        Ok(Self {
+            path: format!("{SYS_CLASS_NET}/{iface}/statistics/{counter}"),
+            last: None,
+            max_mbps,
+        })
+    }
+
+    fn mbps(&mut self) -> Res<f32> {
+        let now = Instant::now();
+        let bytes = fs::read_to_string(&self.path)?.trim().parse::<u64>()?;
+        let val = match self.last {
+            Some((then, last_bytes)) => {
+                let secs = now.duration_since(then).as_secs_f32();
+                if secs > 0.0 {
+                    bytes.saturating_sub(last_bytes) as f32 / secs / 1_000_000.0
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        };
+        self.last = Some((now, bytes));
+        Ok(val)
+    }
+}
+
+// Network receive throughput
+
+#[derive(Debug)]
+pub struct NetRxMbps {
+    pub id: MeterId,
+    throughput: Throughput,
+}
+
+impl NetRxMbps {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        Ok(Self {
+            id,
+            throughput: Throughput::new(source, "rx_bytes")?,
+        })
+    }
+}
+
+impl Meter for NetRxMbps {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        self.throughput.mbps()
+    }
+
+    fn name(&self) -> &str {
+        "Network receive rate"
+    }
+
+    fn unit(&self) -> &str {
+        "MB/s"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, self.throughput.max_mbps)
+    }
+}
+
+// Network transmit throughput
+
+#[derive(Debug)]
+pub struct NetTxMbps {
+    pub id: MeterId,
+    throughput: Throughput,
+}
+
+impl NetTxMbps {
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        Ok(Self {
+            id,
+            throughput: Throughput::new(source, "tx_bytes")?,
+        })
+    }
+}
+
+impl Meter for NetTxMbps {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        self.throughput.mbps()
+    }
+
+    fn name(&self) -> &str {
+        "Network transmit rate"
+    }
+
+    fn unit(&self) -> &str {
+        "MB/s"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, self.throughput.max_mbps)
+    }
+}
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+
+use tiny_http::{Header, Response, Server};
+
+use crate::status::Status;
+use crate::Res;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+// Serve measurements in the Prometheus text format, one gauge per meter.
+pub fn serve(addr: SocketAddr, status: Arc<Status>) -> Res<()> {
+    let server = Server::http(addr)?;
+    let header = Header::from_bytes("Content-Type", CONTENT_TYPE).map_err(|_| "invalid header")?;
+    tracing::info!("prometheus endpoint listening on {addr}");
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = Response::from_string(exposition(&status)).with_header(header.clone());
+            if let Err(err) = request.respond(response) {
+                tracing::warn!("prometheus request error: {err}");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn exposition(status: &Status) -> String {
+    let mut out = String::new();
+    let descriptions = status.named_descriptions();
+    for (name, value) in status.named_measurements() {
+        let metric = metric_name(&name);
+        let help = match descriptions.get(&name) {
+            Some((desc, unit)) if !desc.is_empty() && !unit.is_empty() => {
+                format!("{desc} in {unit}, from the {name} meter.")
+            }
+            Some((desc, _)) if !desc.is_empty() => format!("{desc}, from the {name} meter."),
+            _ => format!("Value of the {name} meter."),
+        };
+        let _ = writeln!(out, "# HELP {metric} {help}");
+        let _ = writeln!(out, "# TYPE {metric} gauge");
+        let _ = writeln!(out, "{metric} {}", format_value(value));
+    }
+    out
+}
+
+// The text format spells non-finite values as +Inf, -Inf and NaN.
+fn format_value(value: f32) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f32::INFINITY {
+        "+Inf".to_string()
+    } else if value == f32::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// Metric names only use letters, digits and underscores, so
+// CPU:PERCENTAGE becomes turing_monitor_cpu_percentage.
+fn metric_name(meter: &str) -> String {
+    let name: String = meter
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect();
+    format!("turing_monitor_{name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter::{Measurements, MeterId};
+
+    #[test]
+    fn test_metric_name() {
+        assert_eq!(
+            metric_name("CPU:PERCENTAGE"),
+            "turing_monitor_cpu_percentage"
+        );
+        assert_eq!(metric_name("NET:ETH0.RX"), "turing_monitor_net_eth0_rx");
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!(format_value(42.5), "42.5");
+        assert_eq!(format_value(-3.0), "-3");
+        assert_eq!(format_value(f32::INFINITY), "+Inf");
+        assert_eq!(format_value(f32::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(f32::NAN), "NaN");
+    }
+
+    #[test]
+    fn test_exposition() {
+        // Without theme names, meters are named after their id.
+        let id = MeterId::from_name("CPU:PERCENTAGE");
+        let status = Status::default();
+        status.update(&Measurements::from([(id, 42.5)]));
+        assert_eq!(
+            exposition(&status),
+            format!(
+                "# HELP turing_monitor_{id} Value of the {id} meter.\n\
+                 # TYPE turing_monitor_{id} gauge\n\
+                 turing_monitor_{id} 42.5\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_exposition_help() {
+        let id = MeterId::from_name("CPU:FREQ_MHZ");
+        let status = Status::default();
+        status.update(&Measurements::from([(id, 3200.0)]));
+        let help = |desc: &str, unit: &str| {
+            status.set_descriptions([(id, (desc.to_string(), unit.to_string()))].into());
+            exposition(&status)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        assert_eq!(
+            help("CPU frequency", "MHz"),
+            format!("# HELP turing_monitor_{id} CPU frequency in MHz, from the {id} meter.")
+        );
+        assert_eq!(
+            help("CPU frequency", ""),
+            format!("# HELP turing_monitor_{id} CPU frequency, from the {id} meter.")
+        );
+        assert_eq!(
+            help("", ""),
+            format!("# HELP turing_monitor_{id} Value of the {id} meter.")
+        );
+    }
+}
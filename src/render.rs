@@ -1,126 +1,958 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::collections::HashMap;
-use std::sync::mpsc;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use turing_screen::{Coord, Font, Image, Rect, Rgba, Screen};
+use turing_screen::{Coord, Image, Rect, Rgba, Screen};
 
-use crate::meter::{Measurements, MeterConfig};
+use crate::dryrun::FrameCapture;
+use crate::fonts::{self, Font, GlyphCache};
+use crate::framebuffer::Framebuffer;
+use crate::mailbox;
+use crate::meter::{Measurements, MeterConfig, MeterId, Ranges};
 use crate::themes;
+use crate::xdg::ResourceLocator;
 use crate::Res;
 
-pub struct Renderer<'a> {
-    ch: mpsc::Receiver<Measurements>,
-    widgets: HashMap<u64, themes::DeviceMeter>,
-    font: HashMap<String, Font<'a>>,
-    scr: Box<dyn Screen>,
-    bg: Image,
+pub const FONTS_DIR: &str = "res/fonts";
+
+// Right-aligned integer in a 3-character field.
+const DEFAULT_FORMAT: &str = "{:>3.0}";
+
+// Largest width and precision accepted in text formats.
+const MAX_FORMAT_WIDTH: usize = 64;
+const MAX_FORMAT_PRECISION: usize = 16;
+
+// Time between attempts to reconnect a screen, doubled after each
+// failure.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+// Messages sent from the scheduler to the renderer.
+pub enum Message {
+    Measurements(Measurements),
+    Reload(themes::Theme, Vec<MeterConfig>, Ranges),
+    Brightness(i32),
+    Idle(bool),
+    Snapshot(mpsc::Sender<Vec<u8>>),
+    Quit,
 }
 
-impl Renderer<'_> {
-    pub fn new(ch: mpsc::Receiver<Measurements>, configs: Vec<MeterConfig>) -> Res<Self> {
-        let mut widgets = HashMap::<u64, themes::DeviceMeter>::new();
-        let mut font_map = HashMap::<String, Font>::new();
-        for cfg in configs {
-            widgets.insert(cfg.id, cfg.layout.clone());
-            if let Some(text) = cfg.layout.text {
-                let font_path = format!("res/fonts/{}", text.font);
+// Theme and brightness last given to a renderer, kept outside of it so
+// a restarted renderer comes back in the same state.
+#[derive(Clone)]
+pub struct RendererState {
+    pub theme: themes::Theme,
+    pub configs: Vec<MeterConfig>,
+    pub ranges: Ranges,
+    pub brightness: i32,
+}
 
-                // don't load fonts twice
-                if font_map.contains_key(&text.font) {
-                    continue;
-                }
+// Stop after rendering a number of frames. The quit flag is raised once
+// every screen has rendered them.
+pub struct FrameLimit {
+    frames: u64,
+    screens: AtomicUsize,
+    quit: Arc<AtomicBool>,
+}
 
-                log::info!("load font {}", font_path);
-                let data = std::fs::read(&font_path)?;
-                let font = Font::from_data(data)?;
-                font_map.insert(text.font, font);
-            }
+impl FrameLimit {
+    pub fn new(frames: u64, screens: usize, quit: Arc<AtomicBool>) -> Self {
+        Self {
+            frames,
+            screens: AtomicUsize::new(screens),
+            quit,
+        }
+    }
+
+    fn reached(&self) {
+        if self.screens.fetch_sub(1, Ordering::SeqCst) == 1 {
+            tracing::info!("frame limit reached");
+            self.quit.store(true, Ordering::Relaxed);
         }
+    }
+}
 
-        let mut scr = turing_screen::new("AUTO")?;
+pub struct Renderer {
+    widgets: HashMap<MeterId, themes::DeviceMeter>,
+    font: HashMap<String, Font>,
+    glyphs: HashMap<(String, u32), GlyphCache>, // by font and size
+    images: HashMap<String, Image>,
+    scr: Box<dyn Screen>,
+    fonts: ResourceLocator,
+    background: PathBuf,
+    bg: Image,
+    fb: Framebuffer,
+    prev: Framebuffer, // framebuffer contents last sent to the screen
+    history: HashMap<MeterId, VecDeque<f32>>,
+    texts: HashMap<MeterId, (String, Rgba, Rect)>, // last text drawn by each widget
+    static_text: Vec<themes::StaticText>,
+    scale: u32,
+    ranges: Ranges,
+    idle: bool,
+    frame: u64,
+    capture: Option<FrameCapture>,
+    frame_limit: Option<Arc<FrameLimit>>,
+    brightness: i32,
+    reconnect: Option<(String, Arc<AtomicBool>)>, // serial port and quit flag
+    refresh_period: Option<Duration>,
+    state: Option<Arc<Mutex<RendererState>>>,
+}
+
+impl Renderer {
+    pub fn new(
+        theme: &themes::Theme,
+        configs: Vec<MeterConfig>,
+        ranges: Ranges,
+        fonts: ResourceLocator,
+        mut scr: Box<dyn Screen>,
+        brightness: i32,
+        scale: u32,
+    ) -> Res<Self> {
         scr.init()?;
         scr.screen_on()?;
-        scr.set_brightness(5)?;
+        scr.set_brightness(brightness)?;
 
-        let (width, height) = scr.screen_size();
+        // The framebuffer keeps the theme orientation, rotated screens
+        // have width and height swapped.
+        let (width, height) = match (scr.screen_size(), theme.rotation()) {
+            ((width, height), 90 | 270) => (height, width),
+            (size, _) => size,
+        };
 
-        log::debug!("framebuffer size: {width}x{height}");
+        tracing::debug!("framebuffer size: {width}x{height}");
         let bg = Image::new(width, height);
+        let mut fb = Framebuffer::new(width, height);
+        fb.set_rotation(theme.rotation());
+        let prev = Framebuffer::new(width, height);
 
-        let renderer = Self {
-            ch,
-            widgets,
-            font: font_map,
+        let mut renderer = Self {
+            widgets: HashMap::new(),
+            font: HashMap::new(),
+            glyphs: HashMap::new(),
+            images: HashMap::new(),
             scr,
+            fonts,
+            background: PathBuf::new(),
             bg,
+            fb,
+            prev,
+            history: HashMap::new(),
+            texts: HashMap::new(),
+            static_text: Vec::new(),
+            scale,
+            ranges,
+            idle: false,
+            frame: 0,
+            capture: None,
+            frame_limit: None,
+            brightness,
+            reconnect: None,
+            refresh_period: None,
+            state: None,
         };
+        renderer.load_theme(theme, configs)?;
 
         Ok(renderer)
     }
 
-    pub fn start(&mut self) -> Res<()> {
-        let bitmap = lodepng::decode32_file("res/themes/Digital_cpu/background_digital.png")?;
-        let mut bg = Image {
-            buffer: bitmap.buffer,
-            width: bitmap.width,
-            height: bitmap.height,
+    // Save each rendered frame, used in dry runs.
+    pub fn set_frame_capture(&mut self, capture: FrameCapture) {
+        self.capture = Some(capture);
+    }
+
+    pub fn set_frame_limit(&mut self, limit: Arc<FrameLimit>) {
+        self.frame_limit = Some(limit);
+    }
+
+    // Record theme reloads and brightness changes in a shared state.
+    pub fn set_state(&mut self, state: Arc<Mutex<RendererState>>) {
+        self.state = Some(state);
+    }
+
+    // Warn when sending a frame to the screen takes longer than this.
+    pub fn set_refresh_period(&mut self, period: Duration) {
+        self.refresh_period = Some(period);
+    }
+
+    // Open the serial port again when the screen stops responding,
+    // until it comes back or the quit flag is raised.
+    pub fn set_reconnect(&mut self, port: String, quit: Arc<AtomicBool>) {
+        self.reconnect = Some((port, quit));
+    }
+
+    // Encode the current screen contents as PNG.
+    pub fn snapshot(&self) -> Res<Vec<u8>> {
+        let png = lodepng::encode32(&self.prev.buffer, self.prev.width, self.prev.height)?;
+        Ok(png)
+    }
+
+    // Set up widgets, fonts and images used by the theme.
+    fn load_theme(&mut self, theme: &themes::Theme, configs: Vec<MeterConfig>) -> Res<()> {
+        self.widgets.clear();
+        self.font.clear();
+        self.glyphs.clear();
+        self.images.clear();
+        self.history.clear();
+        self.texts.clear();
+
+        // Turning a quarter changes the framebuffer size, which is only
+        // set up when the renderer starts.
+        if theme.rotation() % 180 == self.fb.rotation() % 180 {
+            self.fb.set_rotation(theme.rotation());
+        } else {
+            tracing::warn!(
+                "rotation changed from {} to {}, restart to apply",
+                self.fb.rotation(),
+                theme.rotation()
+            );
+        }
+        self.fb.set_flip(theme.flip_h(), theme.flip_v());
+
+        for mut cfg in configs {
+            cfg.layout.scale(self.scale);
+            self.widgets.insert(cfg.id, cfg.layout.clone());
+            if let Some(overlay) = &cfg.layout.image {
+                if !self.images.contains_key(&overlay.path) {
+                    let image_path = theme.dir().join(&overlay.path);
+                    tracing::info!("load image {}", image_path.display());
+                    let img = scale_image(load_png(&image_path)?, self.scale);
+                    self.images.insert(overlay.path.clone(), img);
+                }
+            }
+            if let Some(text) = cfg.layout.text {
+                let font_file = text.font_file();
+                self.load_font(&font_file)?;
+                glyph_cache(&mut self.glyphs, &self.font, &font_file, text.font_size)?;
+                if let Some(size) = text.unit_font_size {
+                    glyph_cache(&mut self.glyphs, &self.font, &font_file, size)?;
+                }
+            }
+        }
+
+        for text in theme.static_text() {
+            self.load_font(&text.font_file())?;
+        }
+        self.static_text = theme.static_text().cloned().collect();
+        for text in &mut self.static_text {
+            text.scale(self.scale);
+        }
+
+        self.background = theme.background_path();
+
+        Ok(())
+    }
+
+    fn load_font(&mut self, font_file: &str) -> Res<()> {
+        // don't load fonts twice
+        if self.font.contains_key(font_file) {
+            return Ok(());
+        }
+        let font_path = match self.fonts.locate(font_file) {
+            Some(path) => path,
+            None => return Err(format!("font not found: {font_file}").into()),
         };
+        tracing::info!("load font {}", font_path.display());
+        let data = std::fs::read(&font_path)?;
+        self.font
+            .insert(font_file.to_string(), Font::from_data(data)?);
+        Ok(())
+    }
+
+    fn draw_background(&mut self) -> Res<()> {
+        tracing::info!("load background {}", self.background.display());
+        let mut bg = scale_image(load_png(&self.background)?, self.scale);
+        self.draw_static_text(&mut bg)?;
 
-        bg.render_on(&mut self.scr, &bg.full(), &Coord::new(0, 0))?;
         self.bg.copy_image(&bg, &bg.full(), &Coord::new(0, 0));
+        self.fb.copy_image(&bg, &bg.full(), &Coord::new(0, 0));
+        self.fb.render_all(&mut self.scr)?;
+        self.prev.copy_image(&bg, &bg.full(), &Coord::new(0, 0));
+
+        Ok(())
+    }
+
+    // Static text becomes part of the background, so it's only drawn
+    // again when the background is.
+    fn draw_static_text(&mut self, bg: &mut Image) -> Res<()> {
+        for text in &self.static_text {
+            let font_file = text.font_file();
+            let cache = glyph_cache(&mut self.glyphs, &self.font, &font_file, text.font_size)?;
+            let pos = Coord::new(text.x as usize, text.y as usize);
+            let (img, rect) = fonts::draw_text(
+                cache,
+                bg,
+                text.font_color,
+                &pos,
+                &text.text,
+                text.rtl,
+                text.antialiased,
+            );
+            bg.copy_image(&img, &img.full(), &Coord::new(rect.x, rect.y));
+        }
+        Ok(())
+    }
+
+    pub fn start(&mut self, ch: &mailbox::Receiver) -> Res<()> {
+        self.draw_background()?;
 
         loop {
-            match self.ch.recv() {
-                Ok(measurements) => {
+            match ch.recv() {
+                Ok(Message::Measurements(measurements)) => {
+                    let limit = self.frame_limit.clone();
+                    if self.idle || limit.as_ref().is_some_and(|l| self.frame >= l.frames) {
+                        continue;
+                    }
                     self.render(measurements);
+                    if let Some(capture) = &mut self.capture {
+                        capture.save_frame()?;
+                    }
+                    if let Some(limit) = limit.filter(|l| self.frame == l.frames) {
+                        limit.reached();
+                    }
+                }
+                Ok(Message::Reload(theme, configs, ranges)) => {
+                    tracing::info!("reload theme");
+                    self.ranges = ranges.clone();
+                    match self
+                        .load_theme(&theme, configs.clone())
+                        .and_then(|_| self.draw_background())
+                    {
+                        Ok(()) => {
+                            if let Some(state) = &self.state {
+                                let mut state = state.lock().unwrap();
+                                (state.theme, state.configs, state.ranges) =
+                                    (theme, configs, ranges);
+                            }
+                        }
+                        Err(err) => tracing::error!("cannot reload theme: {err}"),
+                    }
+                }
+                Ok(Message::Brightness(level)) => {
+                    self.brightness = level;
+                    if let Some(state) = &self.state {
+                        state.lock().unwrap().brightness = level;
+                    }
+                    let res = self.scr.set_brightness(level);
+                    if let Err(err) = self.check_screen(res) {
+                        tracing::error!("cannot set brightness: {err}");
+                    }
+                }
+                Ok(Message::Idle(idle)) => {
+                    self.idle = idle;
+                    let res = if idle {
+                        tracing::info!("idle, turn screen off");
+                        self.scr.screen_off()
+                    } else {
+                        tracing::info!("activity, turn screen on");
+                        self.scr.screen_on()
+                    };
+                    if let Err(err) = self.check_screen(res) {
+                        tracing::error!("cannot set screen state: {err}");
+                    }
+                }
+                Ok(Message::Snapshot(tx)) => match self.snapshot() {
+                    Ok(png) => {
+                        // The requester may have given up waiting.
+                        let _ = tx.send(png);
+                    }
+                    Err(err) => tracing::error!("cannot encode snapshot: {err}"),
+                },
+                Ok(Message::Quit) => {
+                    tracing::info!("turn screen off");
+                    self.scr.screen_off()?;
+                    return Ok(());
                 }
                 Err(err) => {
-                    log::warn!("renderer receive error: {err}");
+                    // The scheduler is gone, nothing else to render.
+                    tracing::warn!("renderer receive error: {err}");
+                    return Err(err.into());
                 }
             }
         }
     }
 
     fn render(&mut self, measurements: Measurements) {
-        log::debug!("measurements: {:?}", measurements);
+        tracing::debug!("measurements: {:?}", measurements);
+        self.frame += 1;
         for (id, value) in measurements {
-            self.render_widget(id, value);
+            if let Err(err) = self.render_widget(id, value) {
+                tracing::warn!("cannot render {id}: {err}");
+            }
+        }
+        if let Err(err) = self.flush() {
+            tracing::warn!("cannot update screen: {err}");
+        }
+    }
+
+    // Send the area changed during this frame to the screen.
+    fn flush(&mut self) -> Res<()> {
+        let rect = match self.fb.diff(&self.prev).rect() {
+            Some(rect) => rect,
+            None => return Ok(()),
+        };
+        let start = Instant::now();
+        let res = self.fb.render_changed(&mut self.scr, &rect);
+        self.check_screen(res)?;
+        let elapsed = start.elapsed();
+        tracing::info!(?elapsed, width = rect.w, height = rect.h, "screen updated");
+        if let Some(period) = self.refresh_period.filter(|&period| elapsed > period) {
+            tracing::warn!(
+                "screen update took {elapsed:?}, longer than the refresh period of {period:?}"
+            );
+        }
+        let pos = Coord::new(rect.x, rect.y);
+        self.prev.copy_image(&self.fb, &rect, &pos);
+        Ok(())
+    }
+
+    // Pass the result of a screen operation through, trying to
+    // reconnect the screen first if it failed.
+    fn check_screen(&mut self, res: Res<()>) -> Res<()> {
+        let err = match res {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        let (port, quit) = match &self.reconnect {
+            Some((port, quit)) => (port.clone(), quit.clone()),
+            None => return Err(err),
+        };
+        tracing::warn!("screen error: {err}, reconnecting to {port}");
+
+        let mut delay = RECONNECT_DELAY;
+        loop {
+            // Sleep in short steps to notice the quit flag.
+            let deadline = Instant::now() + delay;
+            while Instant::now() < deadline {
+                if quit.load(Ordering::Relaxed) {
+                    return Err(err);
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            match self.connect(&port) {
+                Ok(()) => {
+                    tracing::info!("screen reconnected");
+                    return Ok(());
+                }
+                Err(err) => {
+                    tracing::warn!("cannot reconnect to {port}: {err}");
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
         }
     }
 
-    fn render_widget(&mut self, id: u64, value: f32) -> Res<()> {
-        let widget = self.widgets[&id].clone();
+    // Open the screen again and restore its state and contents.
+    fn connect(&mut self, port: &str) -> Res<()> {
+        let mut scr = turing_screen::new(port)?;
+        scr.init()?;
+        if self.idle {
+            scr.screen_off()?;
+        } else {
+            scr.screen_on()?;
+        }
+        scr.set_brightness(self.brightness)?;
+        self.scr = scr;
+        self.fb.render_all(&mut self.scr)?;
+        self.prev
+            .copy_image(&self.fb, &self.fb.full(), &Coord::new(0, 0));
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, id), fields(meter = %id))]
+    fn render_widget(&mut self, id: MeterId, value: f32) -> Res<()> {
+        // Measurements are shared by all screens, skip other widgets.
+        let widget = match self.widgets.get(&id) {
+            Some(w) => w.clone(),
+            None => return Ok(()),
+        };
+        if let Some(overlay) = &widget.image {
+            self.render_image(overlay)?;
+            // The overlay may have covered the text, draw it again. Its
+            // area is kept so it's still cleared.
+            if let Some((last, _, _)) = self.texts.get_mut(&id) {
+                last.clear();
+            }
+        }
+
         if let Some(w) = &widget.text {
-            self.render_text(w, 3, value)?;
+            self.render_text(id, w, value)?;
         } else if let Some(w) = &widget.graph {
-            Self::render_graph(w, value)?;
+            // Graphs have their own scale in the theme.
+            let fraction = match w.min_value.trim().parse::<f32>() {
+                Ok(min) => normalize(value, min, w.max_value as f32),
+                Err(_) => self.fraction(id, value),
+            };
+            self.render_graph(w, value, fraction)?;
+        } else if let Some(w) = &widget.sparkline {
+            let history = self.history.entry(id).or_default();
+            history.push_back(value);
+            while history.len() > w.history as usize {
+                history.pop_front();
+            }
+            self.render_sparkline(id, w)?;
+        } else if let Some(w) = &widget.arc_gauge {
+            self.render_arc_gauge(w, value, self.fraction(id, value))?;
         }
 
         Ok(())
     }
 
-    fn render_text(&mut self, text: &themes::Text, field_size: usize, value: f32) -> Res<()> {
-        let s = format!("{:>size$.*}", 0, value, size = field_size);
-        log::debug!("    Text: {}", s);
+    fn render_text(&mut self, id: MeterId, text: &themes::Text, value: f32) -> Res<()> {
+        let pattern = text.format.as_deref().unwrap_or(DEFAULT_FORMAT);
+        let mut s = format_value(pattern, value)?;
+        if let Some(label) = &text.label {
+            s.insert_str(0, label);
+        }
+        tracing::debug!("    Text: {}", s);
+
+        // Skip unchanged text, unless blinking makes it change every frame.
+        let color = threshold_color(&text.thresholds, value, text.font_color);
+        let blinking = text.blink_above.is_some_and(|limit| value > limit);
+        if !blinking
+            && self
+                .texts
+                .get(&id)
+                .is_some_and(|(last, c, _)| *last == s && *c == color)
+        {
+            return Ok(());
+        }
 
-        if !&self.font.contains_key(&text.font) {
-            return Err(format!("font not loaded: {}", text.font).into());
+        // Clear the previous text, a narrower one wouldn't cover it.
+        if let Some((_, _, last)) = self.texts.remove(&id) {
+            self.fb
+                .copy_image(&self.bg, &last, &Coord::new(last.x, last.y));
         }
 
-        let font = &self.font[&text.font];
-        let size = text.font_size as f32 * 110.0 / 200.0;
-        let color = Rgba::new(0xff, 0, 0, 0xff); // text.font_color;
+        let font_file = text.font_file();
         let pos = Coord::new(text.x as usize, text.y as usize);
+        let cache = glyph_cache(&mut self.glyphs, &self.font, &font_file, text.font_size)?;
+        let ascent = cache.ascent();
+        let value_text =
+            fonts::draw_text(cache, &self.bg, color, &pos, &s, text.rtl, text.antialiased);
+        let end = value_text.1.x + value_text.1.w;
+        let mut parts = vec![value_text];
 
-        let (text_img, bb_rect) = font.draw(&self.bg, size, color, &pos, &s);
-        text_img.render_on(&mut self.scr, &bb_rect, &pos)?;
+        // The unit follows the value, sharing its baseline.
+        if let (true, Some(unit)) = (text.show_unit, &text.unit_label) {
+            let size = text.unit_font_size.unwrap_or(text.font_size);
+            let cache = glyph_cache(&mut self.glyphs, &self.font, &font_file, size)?;
+            let y = pos.y + (ascent - cache.ascent()).max(0.0).round() as usize;
+            let unit_pos = Coord::new(end, y);
+            parts.push(fonts::draw_text(
+                cache,
+                &self.bg,
+                color,
+                &unit_pos,
+                unit,
+                text.rtl,
+                text.antialiased,
+            ));
+        }
+
+        // Blink as an alarm, showing only the background on odd frames.
+        // Text goes through the framebuffer like other widgets, so it
+        // shows in snapshots.
+        if blinking && self.frame % 2 == 1 {
+            for (_, rect) in &parts {
+                self.fb
+                    .copy_image(&self.bg, rect, &Coord::new(rect.x, rect.y));
+            }
+        } else {
+            for (img, rect) in &parts {
+                self.fb
+                    .copy_image(img, &img.full(), &Coord::new(rect.x, rect.y));
+            }
+            let bounds = parts
+                .iter()
+                .map(|(_, rect)| Rect::new(rect.x, rect.y, rect.w, rect.h))
+                .reduce(|a, b| union(&a, &b))
+                .unwrap_or(Rect::new(0, 0, 0, 0));
+            self.texts.insert(id, (s, color, bounds));
+        }
+
+        Ok(())
+    }
+
+    // Scale a value to the 0-1 range using the meter's expected range.
+    fn fraction(&self, id: MeterId, value: f32) -> f32 {
+        let (min, max) = self.ranges.get(&id).copied().unwrap_or((0.0, 100.0));
+        normalize(value, min, max)
+    }
+
+    fn render_graph(&mut self, graph: &themes::Graph, value: f32, fraction: f32) -> Res<()> {
+        tracing::debug!("    Graph: {}", value);
+
+        let (x, y) = (graph.x as usize, graph.y as usize);
+        let (width, height) = (graph.width as usize, graph.height as usize);
+        let rect = Rect::new(x, y, width, height);
+        let pos = Coord::new(x, y);
+
+        // Clear the graph area so a shrinking bar doesn't leave a trail.
+        match graph.background_color {
+            Some(color) => self.fb.fill_rect(&rect, color),
+            None => self.fb.copy_image(&self.bg, &rect, &pos),
+        }
+
+        let color = threshold_color(&graph.thresholds, value, graph.bar_color);
+        let bar_width = (width as f32 * fraction).round() as usize;
+        match graph.gradient {
+            // The gradient spans the whole graph, so each column keeps
+            // its color as the bar grows.
+            Some((low, high)) if height > 0 => {
+                let span = width.saturating_sub(1).max(1) as f32;
+                for col in 0..bar_width {
+                    let color = lerp_color(low, high, col as f32 / span);
+                    self.fb.draw_vline(x + col, y, y + height - 1, color);
+                }
+            }
+            _ => self
+                .fb
+                .fill_rect(&Rect::new(x, y, bar_width, height), color),
+        }
+
+        if graph.bar_outline && width > 0 && height > 0 {
+            let (x1, y1) = (x + width - 1, y + height - 1);
+            self.fb.draw_hline(y, x, x1, color);
+            self.fb.draw_hline(y1, x, x1, color);
+            self.fb.draw_vline(x, y, y1, color);
+            self.fb.draw_vline(x1, y, y1, color);
+        }
+
+        Ok(())
+    }
+
+    fn render_sparkline(&mut self, id: MeterId, sparkline: &themes::Sparkline) -> Res<()> {
+        let (x, y) = (sparkline.x as usize, sparkline.y as usize);
+        let (width, height) = (sparkline.width as usize, sparkline.height as usize);
+        let rect = Rect::new(x, y, width, height);
+        let pos = Coord::new(x, y);
+
+        match sparkline.background_color {
+            Some(color) => self.fb.fill_rect(&rect, color),
+            None => self.fb.copy_image(&self.bg, &rect, &pos),
+        }
+
+        let history: Vec<f32> = self.history[&id]
+            .iter()
+            .map(|&val| self.fraction(id, val))
+            .collect();
+        tracing::debug!("    Sparkline: {:?}", self.history[&id]);
+        if width < 2 || height < 1 || sparkline.history < 2 {
+            return Ok(());
+        }
+
+        // Oldest value on the left, newest on the right.
+        let step = (width - 1) as f32 / (sparkline.history - 1) as f32;
+        let start = sparkline.history as usize - history.len();
+        let points: Vec<(usize, usize)> = history
+            .iter()
+            .enumerate()
+            .map(|(i, fraction)| {
+                let px = x + ((start + i) as f32 * step).round() as usize;
+                let py = y + height - 1 - ((height - 1) as f32 * fraction).round() as usize;
+                (px, py)
+            })
+            .collect();
+
+        for pair in points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            self.fb.draw_line(x0, y0, x1, y1, sparkline.line_color);
+        }
 
         Ok(())
     }
 
-    fn render_graph(_graph: &themes::Graph, value: f32) -> Res<()> {
-        log::debug!("    Graph: {}", value);
+    fn render_arc_gauge(&mut self, gauge: &themes::ArcGauge, value: f32, fraction: f32) -> Res<()> {
+        tracing::debug!("    ArcGauge: {}", value);
+
+        let (cx, cy) = (gauge.x as isize, gauge.y as isize);
+        let radius = gauge.radius as isize;
+        let inner = (radius - gauge.thickness as isize).max(0);
+        let x = (cx - radius).max(0) as usize;
+        let y = (cy - radius).max(0) as usize;
+        let size = (2 * radius + 1) as usize;
+        let rect = Rect::new(x, y, size, size);
+        let pos = Coord::new(x, y);
+
+        self.fb.copy_image(&self.bg, &rect, &pos);
+
+        // Angles are in degrees, clockwise from 3 o'clock.
+        let range = (gauge.end_angle - gauge.start_angle).clamp(0.0, 360.0);
+        let sweep = range * fraction;
+
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let d2 = dx * dx + dy * dy;
+                if d2 > radius * radius || d2 < inner * inner {
+                    continue;
+                }
+                let angle = (dy as f32).atan2(dx as f32).to_degrees();
+                let rel = (angle - gauge.start_angle).rem_euclid(360.0);
+                let color = if rel <= sweep {
+                    gauge.color
+                } else if rel <= range {
+                    match gauge.track_color {
+                        Some(color) => color,
+                        None => continue,
+                    }
+                } else {
+                    continue;
+                };
+                let (px, py) = (cx + dx, cy + dy);
+                if px >= 0 && py >= 0 {
+                    self.fb.set_pixel(px as usize, py as usize, color);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn render_image(&mut self, overlay: &themes::Image) -> Res<()> {
+        let img = match self.images.get(&overlay.path) {
+            Some(img) => img,
+            None => return Err(format!("image not loaded: {}", overlay.path).into()),
+        };
+
+        let (x, y) = (overlay.x as usize, overlay.y as usize);
+        let rect = Rect::new(x, y, img.width, img.height);
+        let pos = Coord::new(x, y);
+
+        // Start from the background so blending doesn't accumulate.
+        self.fb.copy_image(&self.bg, &rect, &pos);
+        self.fb.composite_image(img, &pos);
         Ok(())
     }
 }
+
+// Pixel size used to draw a theme font size.
+fn font_pixels(font_size: u32) -> f32 {
+    font_size as f32 * 110.0 / 200.0
+}
+
+// Glyphs of a loaded font at a theme font size, rasterized on first use.
+fn glyph_cache<'a>(
+    glyphs: &'a mut HashMap<(String, u32), GlyphCache>,
+    font: &HashMap<String, Font>,
+    font_file: &str,
+    font_size: u32,
+) -> Res<&'a mut GlyphCache> {
+    let font = font
+        .get(font_file)
+        .ok_or_else(|| format!("font not loaded: {font_file}"))?;
+    Ok(glyphs
+        .entry((font_file.to_string(), font_size))
+        .or_insert_with(|| font.prerender_ascii(font_pixels(font_size))))
+}
+
+// Use the color of the highest threshold exceeded by the value.
+fn threshold_color(thresholds: &[(f32, Rgba)], value: f32, default: Rgba) -> Rgba {
+    thresholds
+        .iter()
+        .take_while(|(threshold, _)| value > *threshold)
+        .last()
+        .map_or(default, |&(_, color)| color)
+}
+
+// Scale a value to the 0-1 range between min and max.
+fn normalize(value: f32, min: f32, max: f32) -> f32 {
+    if max <= min {
+        return 0.0;
+    }
+    ((value - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+// Smallest rectangle containing both rectangles.
+fn union(a: &Rect, b: &Rect) -> Rect {
+    let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+    let x_end = (a.x + a.w).max(b.x + b.w);
+    let y_end = (a.y + a.h).max(b.y + b.h);
+    Rect::new(x, y, x_end - x, y_end - y)
+}
+
+// Interpolate between two colors, t going from 0 to 1.
+fn lerp_color(a: Rgba, b: Rgba, t: f32) -> Rgba {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Rgba::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b), mix(a.a, b.a))
+}
+
+fn load_png(path: &Path) -> Res<Image> {
+    let bitmap = lodepng::decode32_file(path)?;
+    Ok(Image {
+        buffer: bitmap.buffer,
+        width: bitmap.width,
+        height: bitmap.height,
+    })
+}
+
+// Enlarge an image by an integer factor, repeating each pixel.
+fn scale_image(img: Image, factor: u32) -> Image {
+    let factor = factor as usize;
+    if factor <= 1 {
+        return img;
+    }
+    let width = img.width * factor;
+    let mut buffer = Vec::with_capacity(img.buffer.len() * factor * factor);
+    for row in img.buffer.chunks(img.width) {
+        let line: Vec<Rgba> = row
+            .iter()
+            .flat_map(|&px| std::iter::repeat(px).take(factor))
+            .collect();
+        for _ in 0..factor {
+            buffer.extend_from_slice(&line);
+        }
+    }
+    Image {
+        buffer,
+        width,
+        height: img.height * factor,
+    }
+}
+
+// Format a value using a Rust-like pattern such as "{:>5.1}°". Only
+// fill, alignment, width and precision are supported in the
+// placeholder spec.
+fn format_value(pattern: &str, value: f32) -> Res<String> {
+    let mut out = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut spec = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => spec.push(c),
+                        None => return Err(format!("unterminated format: {pattern}").into()),
+                    }
+                }
+                out.push_str(&format_spec(&spec, value)?);
+            }
+            _ => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn format_spec(spec_str: &str, value: f32) -> Res<String> {
+    let invalid = || format!("invalid format spec: {{{spec_str}}}");
+    let spec: Vec<char> = spec_str
+        .strip_prefix(':')
+        .unwrap_or(spec_str)
+        .chars()
+        .collect();
+    let is_align = |c: char| "<>^".contains(c);
+
+    // [[fill]align][width][.precision]
+    let mut i = 0;
+    let mut fill = ' ';
+    let mut align = '>';
+    if spec.len() >= 2 && is_align(spec[1]) {
+        fill = spec[0];
+        align = spec[1];
+        i = 2;
+    } else if !spec.is_empty() && is_align(spec[0]) {
+        align = spec[0];
+        i = 1;
+    }
+
+    if let Some(&flag) = spec.get(i).filter(|&&c| "+-0#".contains(c)) {
+        return Err(format!("unsupported flag '{flag}' in format spec: {{{spec_str}}}").into());
+    }
+
+    // Numbers are limited, the text is formatted on every frame.
+    let number = |i: &mut usize, max: usize| -> Res<Option<usize>> {
+        let start = *i;
+        while *i < spec.len() && spec[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        if start == *i {
+            return Ok(None);
+        }
+        match spec[start..*i].iter().collect::<String>().parse() {
+            Ok(n) if n <= max => Ok(Some(n)),
+            _ => Err(format!("format spec {{{spec_str}}} exceeds the limit of {max}").into()),
+        }
+    };
+
+    let width = number(&mut i, MAX_FORMAT_WIDTH)?.unwrap_or(0);
+    let mut precision = None;
+    if i < spec.len() && spec[i] == '.' {
+        i += 1;
+        precision = Some(number(&mut i, MAX_FORMAT_PRECISION)?.ok_or_else(invalid)?);
+    }
+    if i != spec.len() {
+        return Err(invalid().into());
+    }
+
+    let s = match precision {
+        Some(p) => format!("{:.*}", p, value),
+        None => format!("{}", value),
+    };
+
+    let pad = width.saturating_sub(s.chars().count());
+    let fill_str = |n: usize| fill.to_string().repeat(n);
+    let res = match align {
+        '<' => format!("{s}{}", fill_str(pad)),
+        '^' => format!("{}{s}{}", fill_str(pad / 2), fill_str(pad - pad / 2)),
+        _ => format!("{}{s}", fill_str(pad)),
+    };
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_value() {
+        let cases = [
+            ("{}", 42.5, "42.5"),
+            ("{:>5.1}°", 42.31, " 42.3°"),
+            ("{:<5.0}|", 7.0, "7    |"),
+            ("{:^6.1}", 1.0, " 1.0  "),
+            ("{:*>6.2}", 3.14159, "**3.14"),
+            ("{:0>4.0}", 7.0, "0007"),
+            ("{:2}", 100.5, "100.5"),
+            ("{{{:.0}}}", 5.0, "{5}"),
+            ("}}{{", 0.0, "}{"),
+            ("no value", 1.0, "no value"),
+        ];
+        for (pattern, value, expected) in cases {
+            assert_eq!(format_value(pattern, value).unwrap(), expected, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn test_format_value_errors() {
+        let cases = [
+            "{:.0",
+            "{:x}",
+            "{:5.}",
+            "{:+.1}",
+            "{:05}",
+            "{:#}",
+            "{:-}",
+            "{:999999999}",
+            "{:.999999999}",
+            "{:99999999999999999999999}",
+        ];
+        for pattern in cases {
+            assert!(format_value(pattern, 1.0).is_err(), "{pattern}");
+        }
+    }
+}
@@ -1,15 +1,36 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::sync::mpsc;
+use std::cmp::Reverse;
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::meter::{Measurements, Meter};
+use rand::Rng;
+
+use crate::als::AmbientLight;
+use crate::brightness::Schedule;
+use crate::expr::Expr;
+use crate::mailbox;
+use crate::meter::{Descriptions, Measurements, Meter, MeterId, Ranges};
+use crate::render::Message;
+use crate::state;
+use crate::status::Status;
 
 pub struct Task {
     meter: Box<dyn Meter>,
     period: Duration,
+    jitter: Duration,
+    wait: Duration,
+    priority: u8,
     last: Instant,
+    alpha: f32,
+    primed: bool,
+    min: f32,
+    max: f32,
+    transform: Option<Expr>,
+    on_error: Option<Box<dyn Fn(&dyn Error) + Send>>,
 }
 
 impl Task {
@@ -17,71 +38,575 @@ impl Task {
         Self {
             meter,
             period,
+            jitter: Duration::ZERO,
+            wait: period,
+            priority: 0,
             last: Instant::now() - Duration::from_secs(86400), // a long time ago
+            alpha: 1.0,
+            primed: false,
+            min: f32::NEG_INFINITY,
+            max: f32::INFINITY,
+            transform: None,
+            on_error: None,
+        }
+    }
+
+    // Tasks with higher priority run first when several are due.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    // Randomly vary the interval between measurements by up to the given
+    // amount, so meters with the same period don't all read at once.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter.min(self.period);
+        self
+    }
+
+    fn next_wait(&self) -> Duration {
+        if self.jitter.is_zero() {
+            return self.period;
+        }
+        let jitter = self.jitter.as_secs_f32();
+        let offset = rand::thread_rng().gen_range(-jitter..=jitter);
+        Duration::from_secs_f32((self.period.as_secs_f32() + offset).max(0.0))
+    }
+
+    // Transform, clamp and smooth a new measurement.
+    fn process(&self, val: f32, last: f32) -> f32 {
+        let val = match &self.transform {
+            Some(expr) => expr.eval(val),
+            None => val,
+        };
+        let val = clamp(val, self.min, self.max);
+        if !self.primed {
+            return val;
+        }
+        // Clamp again in case rounding pushed the average out of range.
+        clamp(
+            self.alpha * val + (1.0 - self.alpha) * last,
+            self.min,
+            self.max,
+        )
+    }
+
+    // Take a measurement and store the processed values.
+    #[tracing::instrument(level = "debug", skip_all, fields(meter = %self.meter.id()))]
+    fn run(&mut self, meter_map: &mut Measurements) {
+        let values = match self.meter.measure_many() {
+            Ok(values) => values,
+            Err(err) => {
+                match &self.on_error {
+                    Some(on_error) => on_error(err.as_ref()),
+                    None => tracing::warn!("measurement error: {}", err),
+                }
+                vec![(self.meter.id(), 0.0)]
+            }
+        };
+
+        for (id, val) in values {
+            if let Some(slot) = meter_map.get_mut(&id) {
+                *slot = self.process(val, *slot);
+            }
         }
+        self.primed = true;
+    }
+
+    // Call the given function when a measurement fails, instead of
+    // logging a warning.
+    pub fn with_error_handler(mut self, on_error: Box<dyn Fn(&dyn Error) + Send>) -> Self {
+        self.on_error = Some(on_error);
+        self
+    }
+
+    // Apply an expression to measurements before clamping.
+    pub fn with_transform(mut self, transform: Expr) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    // Clamp measurements to the given range.
+    pub fn with_limits(mut self, min: f32, max: f32) -> Self {
+        self.min = min;
+        self.max = max.max(min);
+        self
+    }
+
+    // Smooth measurements with an exponential moving average. The
+    // smoothing factor is a time constant in seconds.
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        if smoothing > 0.0 {
+            let dt = self.period.max(Duration::from_millis(100)).as_secs_f32();
+            self.alpha = 1.0 - (-dt / smoothing).exp();
+        }
+        self
     }
 }
 
+// Clamp a value to the given range. NaN, from a failed sensor read or a
+// bad transform, is treated as zero.
+fn clamp(val: f32, min: f32, max: f32) -> f32 {
+    if val.is_nan() {
+        return 0.0_f32.clamp(min, max);
+    }
+    val.clamp(min, max)
+}
+
+// Time between the warm-up and the actual reading in run_once.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Why the scheduler stopped running tasks.
+#[derive(Debug, PartialEq)]
+pub enum Stop {
+    Reload,
+    Quit,
+}
+
 pub struct Scheduler {
-    ch: mpsc::SyncSender<Measurements>,
+    ch: Vec<mailbox::Sender>,
     refresh_period: Duration,
     tasks: Vec<Task>,
+    reload: Arc<AtomicBool>,
+    quit: Arc<AtomicBool>,
+    brightness: Option<Schedule>,
+    ambient_light: Option<AmbientLight>,
+    brightness_level: Option<i32>,
+    idle: Option<mpsc::Receiver<bool>>,
+    status: Option<Arc<Status>>,
+    paused: Arc<AtomicBool>,
+    save_state: bool,
 }
 
 impl Scheduler {
-    pub fn new(ch: mpsc::SyncSender<Measurements>, period: Duration) -> Self {
+    pub fn new(
+        ch: Vec<mailbox::Sender>,
+        period: Duration,
+        reload: Arc<AtomicBool>,
+        quit: Arc<AtomicBool>,
+    ) -> Self {
         Self {
             ch,
             refresh_period: period,
             tasks: Vec::new(),
+            reload,
+            quit,
+            brightness: None,
+            ambient_light: None,
+            brightness_level: None,
+            idle: None,
+            status: None,
+            paused: Arc::new(AtomicBool::new(false)),
+            save_state: true,
         }
     }
 
+    // Stop running tasks and sending measurements until resumed.
+    pub fn pause(&self) {
+        tracing::debug!("pause scheduler");
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        tracing::debug!("resume scheduler");
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    // Flag to pause the scheduler from another thread while it runs.
+    pub fn pause_handle(&self) -> Arc<AtomicBool> {
+        self.paused.clone()
+    }
+
+    // Adjust screen brightness according to the time of day.
+    pub fn set_brightness_schedule(&mut self, schedule: Schedule) {
+        self.brightness_level = Some(schedule.current_level());
+        self.brightness = Some(schedule);
+    }
+
+    // Adjust screen brightness according to the ambient light. This takes
+    // precedence over the time of day schedule.
+    pub fn set_ambient_light(&mut self, sensor: AmbientLight, level: i32) {
+        self.brightness_level = Some(level);
+        self.ambient_light = Some(sensor);
+    }
+
+    // Turn screens off and on when idle state changes.
+    pub fn set_idle_watch(&mut self, rx: mpsc::Receiver<bool>) {
+        self.idle = Some(rx);
+    }
+
+    // Save brightness changes to restore them after a crash.
+    pub fn set_save_state(&mut self, save_state: bool) {
+        self.save_state = save_state;
+    }
+
+    // Publish measurements to status servers.
+    pub fn set_status(&mut self, status: Arc<Status>) {
+        self.status = Some(status);
+    }
+
     pub fn register_task(&mut self, task: Task) {
-        log::info!("register {}", task.meter.id());
+        tracing::info!("register {}", task.meter.id());
         self.tasks.push(task);
+        // Keep the task list in dispatch order. The sort is stable, so
+        // tasks with the same priority run in registration order.
+        self.tasks.sort_by_key(|task| Reverse(task.priority));
+    }
+
+    // Remove the task running the given meter, returning true if found.
+    pub fn deregister_task(&mut self, id: MeterId) -> bool {
+        let len = self.tasks.len();
+        self.tasks.retain(|task| task.meter.id() != id);
+        if self.tasks.len() == len {
+            return false;
+        }
+        tracing::info!("deregister {id}");
+        true
+    }
+
+    pub fn clear_tasks(&mut self) {
+        self.tasks.clear();
     }
 
-    pub fn start(&mut self, mut meter_map: Measurements) {
-        log::info!("start scheduler");
+    // Get the expected value range of each registered meter. Limits set
+    // in the theme take precedence over the meter's own range.
+    pub fn ranges(&self) -> Ranges {
+        self.tasks
+            .iter()
+            .map(|task| {
+                let (min, max) = task.meter.range();
+                let min = if task.min.is_finite() { task.min } else { min };
+                let max = if task.max.is_finite() { task.max } else { max };
+                (task.meter.id(), (min, max))
+            })
+            .collect()
+    }
+
+    // Get the description and unit of each registered meter.
+    pub fn descriptions(&self) -> Descriptions {
+        self.tasks
+            .iter()
+            .map(|task| {
+                let meter = &task.meter;
+                (
+                    meter.id(),
+                    (meter.name().to_string(), meter.unit().to_string()),
+                )
+            })
+            .collect()
+    }
+
+    // Run tasks until a reload or quit is requested.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn start(&mut self, mut meter_map: Measurements) -> Stop {
+        tracing::info!("start scheduler");
         let mut last_refresh = Instant::now() - Duration::from_secs(86400); // a long time ago;
 
         loop {
+            if self.quit.load(Ordering::Relaxed) {
+                tracing::info!("stop scheduler");
+                return Stop::Quit;
+            }
+            if self.reload.swap(false, Ordering::Relaxed) {
+                tracing::info!("stop scheduler for reload");
+                return Stop::Reload;
+            }
+
+            if self.paused.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+
+            if let Some(rx) = &self.idle {
+                while let Ok(idle) = rx.try_recv() {
+                    for ch in &self.ch {
+                        if let Err(err) = ch.send(Message::Idle(idle)) {
+                            tracing::info!("scheduler send error: {err}");
+                        }
+                    }
+                }
+            }
+
             let now = Instant::now();
 
             // Collect stats from meters
             for task in &mut self.tasks {
-                let m = &mut task.meter;
-
                 // if timer expired, run our task
-                if task.last.elapsed() >= task.period {
+                if task.last.elapsed() >= task.wait {
                     task.last = now;
-                    let val = match m.measure() {
-                        Ok(val) => val,
-                        Err(err) => {
-                            log::warn!("measurement error: {}", err);
-                            0.0
-                        }
-                    };
-
-                    if let Some(slot) = meter_map.get_mut(&m.id()) {
-                        *slot = val;
-                    }
+                    task.wait = task.next_wait();
+                    task.run(&mut meter_map);
                 }
             }
 
-            // Send state to renderer
+            // Send state to renderers
             if last_refresh.elapsed() >= self.refresh_period {
-                match self.ch.try_send(meter_map.clone()) {
-                    Ok(_) => {}
-                    Err(err) => {
-                        log::info!("scheduler send error: {err}");
-                    }
-                }
+                self.publish(&meter_map);
                 last_refresh = now;
+                self.update_brightness();
             }
 
             thread::sleep(Duration::from_millis(100));
         }
     }
+
+    // Measure every meter once and send the results to renderers. Rate
+    // meters such as CPU usage need two readings, so every meter is
+    // sampled and discarded first.
+    pub fn run_once(&mut self, meter_map: &mut Measurements) {
+        for task in &mut self.tasks {
+            let _ = task.meter.measure_many();
+        }
+        thread::sleep(SAMPLE_INTERVAL);
+        for task in &mut self.tasks {
+            task.run(meter_map);
+        }
+        self.publish(meter_map);
+    }
+
+    fn publish(&self, meter_map: &Measurements) {
+        if let Some(status) = &self.status {
+            status.update(meter_map);
+        }
+        for ch in &self.ch {
+            if let Err(err) = ch.post(meter_map.clone()) {
+                tracing::info!("scheduler send error: {err}");
+            }
+        }
+    }
+
+    fn update_brightness(&mut self) {
+        let (level, threshold) = if let Some(sensor) = &self.ambient_light {
+            match sensor.level() {
+                Ok(level) => (level, sensor.threshold),
+                Err(err) => {
+                    tracing::warn!("cannot read ambient light sensor: {err}");
+                    return;
+                }
+            }
+        } else if let Some(schedule) = &self.brightness {
+            (schedule.current_level(), 0)
+        } else {
+            return;
+        };
+
+        // Ignore small changes in sensor readings.
+        if let Some(last) = self.brightness_level {
+            if (level - last).abs() <= threshold {
+                return;
+            }
+        }
+        tracing::debug!("set brightness to {level}");
+        for ch in &self.ch {
+            if let Err(err) = ch.send(Message::Brightness(level)) {
+                tracing::info!("scheduler send error: {err}");
+            }
+        }
+        self.brightness_level = Some(level);
+        if self.save_state {
+            state::save_brightness(level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    use proptest::prelude::*;
+
+    use crate::Res;
+
+    // Count calls and stop the scheduler once the deadline has passed.
+    struct MockMeter {
+        id: MeterId,
+        calls: Arc<AtomicUsize>,
+        deadline: Instant,
+        quit: Arc<AtomicBool>,
+    }
+
+    impl Meter for MockMeter {
+        fn id(&self) -> MeterId {
+            self.id
+        }
+
+        fn measure(&mut self) -> Res<f32> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            if Instant::now() >= self.deadline {
+                self.quit.store(true, Ordering::Relaxed);
+            }
+            Ok(1.0)
+        }
+    }
+
+    fn scheduler(quit: Arc<AtomicBool>) -> Scheduler {
+        let reload = Arc::new(AtomicBool::new(false));
+        Scheduler::new(Vec::new(), Duration::from_secs(60), reload, quit)
+    }
+
+    #[test]
+    fn test_dispatch_timing() {
+        let quit = Arc::new(AtomicBool::new(false));
+        let mut scheduler = scheduler(quit.clone());
+
+        let duration = Duration::from_millis(1200);
+        let start = Instant::now();
+        let deadline = start + duration;
+        let intervals = [200, 300, 600].map(Duration::from_millis);
+        let mut counters = Vec::new();
+        for (i, interval) in intervals.iter().enumerate() {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let meter = MockMeter {
+                id: MeterId(i as u64),
+                calls: calls.clone(),
+                deadline,
+                quit: quit.clone(),
+            };
+            scheduler.register_task(Task::new(Box::new(meter), *interval));
+            counters.push(calls);
+        }
+
+        let meter_map = (0..intervals.len())
+            .map(|i| (MeterId(i as u64), 0.0))
+            .collect();
+        assert_eq!(scheduler.start(meter_map), Stop::Quit);
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= duration);
+        for (interval, calls) in intervals.iter().zip(counters) {
+            let expected = (duration.as_millis() / interval.as_millis()) as usize;
+            let calls = calls.load(Ordering::Relaxed);
+            assert!(
+                calls >= expected,
+                "{interval:?}: {calls} calls, expected at least {expected}"
+            );
+            // Tasks never run more often than their interval allows.
+            let limit = (elapsed.as_millis() / interval.as_millis()) as usize + 1;
+            assert!(calls <= limit, "{interval:?}: {calls} calls, limit {limit}");
+        }
+    }
+
+    #[test]
+    fn test_quit_before_dispatch() {
+        let quit = Arc::new(AtomicBool::new(true));
+        let mut scheduler = scheduler(quit.clone());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let meter = MockMeter {
+            id: MeterId(0),
+            calls: calls.clone(),
+            deadline: Instant::now(),
+            quit,
+        };
+        scheduler.register_task(Task::new(Box::new(meter), Duration::ZERO));
+
+        assert_eq!(scheduler.start(Measurements::new()), Stop::Quit);
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_pause_from_thread() {
+        let quit = Arc::new(AtomicBool::new(false));
+        let mut scheduler = scheduler(quit.clone());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let meter = MockMeter {
+            id: MeterId(0),
+            calls: calls.clone(),
+            deadline: Instant::now() + Duration::from_secs(60),
+            quit: quit.clone(),
+        };
+        scheduler.register_task(Task::new(Box::new(meter), Duration::ZERO));
+        let paused = scheduler.pause_handle();
+
+        let control = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            paused.store(true, Ordering::Relaxed);
+            // Let a dispatch already in progress finish.
+            thread::sleep(Duration::from_millis(200));
+            let before = calls.load(Ordering::Relaxed);
+            thread::sleep(Duration::from_millis(500));
+            let during = calls.load(Ordering::Relaxed) - before;
+            paused.store(false, Ordering::Relaxed);
+            thread::sleep(Duration::from_millis(300));
+            let after = calls.load(Ordering::Relaxed) - before - during;
+            quit.store(true, Ordering::Relaxed);
+            (before, during, after)
+        });
+
+        assert_eq!(scheduler.start(Measurements::new()), Stop::Quit);
+        let (before, during, after) = control.join().unwrap();
+        assert!(before > 0);
+        assert_eq!(during, 0);
+        assert!(after > 0);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let quit = Arc::new(AtomicBool::new(false));
+        let mut scheduler = scheduler(quit);
+        scheduler.register_task(
+            Task::new(Box::new(ConstMeter), Duration::ZERO).with_limits(10.0, f32::INFINITY),
+        );
+        // The theme minimum replaces the meter's, the maximum is kept.
+        assert_eq!(scheduler.ranges()[&MeterId(0)], (10.0, 100.0));
+    }
+
+    struct ConstMeter;
+
+    impl Meter for ConstMeter {
+        fn id(&self) -> MeterId {
+            MeterId(0)
+        }
+
+        fn measure(&mut self) -> Res<f32> {
+            Ok(0.0)
+        }
+    }
+
+    // Finite limits, in any order.
+    fn limits() -> impl Strategy<Value = (f32, f32)> {
+        (-1e6_f32..1e6, -1e6_f32..1e6).prop_map(|(a, b)| (a.min(b), a.max(b)))
+    }
+
+    // Any value including NaN, infinities and subnormals.
+    fn value() -> impl Strategy<Value = f32> {
+        prop_oneof![
+            proptest::num::f32::ANY,
+            Just(f32::NAN),
+            Just(f32::INFINITY),
+            Just(f32::NEG_INFINITY),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_clamp_within_limits((min, max) in limits(), val in value()) {
+            let task = Task::new(Box::new(ConstMeter), Duration::ZERO).with_limits(min, max);
+            let res = task.process(val, 0.0);
+            prop_assert!(min <= res && res <= max, "{val} -> {res} not in [{min}, {max}]");
+        }
+
+        #[test]
+        fn test_smoothed_clamp_within_limits(
+            (min, max) in limits(),
+            values in proptest::collection::vec(value(), 1..20),
+            smoothing in 0.0_f32..100.0,
+        ) {
+            let mut task = Task::new(Box::new(ConstMeter), Duration::from_secs(1))
+                .with_smoothing(smoothing)
+                .with_limits(min, max);
+            let mut res = 0.0;
+            for val in values {
+                res = task.process(val, res);
+                task.primed = true;
+                prop_assert!(min <= res && res <= max, "{val} -> {res} not in [{min}, {max}]");
+            }
+        }
+
+        #[test]
+        fn test_unlimited_keeps_value(val in proptest::num::f32::NORMAL) {
+            let task = Task::new(Box::new(ConstMeter), Duration::ZERO);
+            prop_assert_eq!(task.process(val, 0.0), val);
+        }
+    }
 }
@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::status::Status;
+use crate::Res;
+
+// Time a client has to send its request line.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Answer each newline sent to the socket with the current measurements
+// as JSON, then close the connection.
+pub fn serve(path: &Path, status: Arc<Status>) -> Res<()> {
+    // Remove the socket left by a previous run, but nothing else.
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => return Err(format!("{} exists and is not a socket", path.display()).into()),
+        Err(_) => (),
+    }
+    let listener = UnixListener::bind(path)?;
+    tracing::info!("listening on {}", path.display());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let res = match stream {
+                Ok(stream) => handle(stream, &status),
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = res {
+                tracing::warn!("socket client error: {err}");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle(stream: UnixStream, status: &Status) -> Res<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    writeln!(&stream, "{}", status.to_json())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn test_keep_regular_file() {
+        let path = std::env::temp_dir().join(format!("turing-monitor-socket-{}", process::id()));
+        fs::write(&path, "data").unwrap();
+        assert!(serve(&path, Arc::new(Status::default())).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "data");
+        fs::remove_file(&path).unwrap();
+    }
+}
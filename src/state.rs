@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Res;
+
+const PID_FILE: &str = "turing-monitor.pid";
+const STATE_FILE: &str = "turing-monitor.state";
+
+// Screen state saved across runs.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct State {
+    pub brightness: Option<i32>,
+}
+
+fn runtime_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => env::temp_dir(),
+    }
+}
+
+pub fn load() -> Res<State> {
+    let data = fs::read_to_string(runtime_dir().join(STATE_FILE))?;
+    Ok(toml::from_str(&data)?)
+}
+
+pub fn save(state: &State) -> Res<()> {
+    fs::write(runtime_dir().join(STATE_FILE), toml::to_string(state)?)?;
+    Ok(())
+}
+
+// Save the brightness level, logging errors.
+pub fn save_brightness(level: i32) {
+    let state = State {
+        brightness: Some(level),
+    };
+    if let Err(err) = save(&state) {
+        tracing::warn!("cannot save state: {err}");
+    }
+}
+
+// The PID file is removed on a clean exit, so finding a stale one on
+// startup means the previous run crashed.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    // Create the PID file, returning true if the previous run didn't
+    // exit cleanly.
+    pub fn create() -> Res<(Self, bool)> {
+        let path = runtime_dir().join(PID_FILE);
+        let crashed = match fs::read_to_string(&path) {
+            Ok(data) => match data.trim().parse() {
+                Ok(pid) if is_running(pid) => {
+                    return Err(format!("already running with pid {pid}").into());
+                }
+                _ => true,
+            },
+            Err(_) => false,
+        };
+        fs::write(&path, format!("{}\n", process::id()))?;
+        Ok((Self { path }, crashed))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn is_running(pid: u32) -> bool {
+    pid != process::id() && Path::new(&format!("/proc/{pid}")).exists()
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use arc_swap::ArcSwapOption;
+
+use crate::meter::{Descriptions, Measurements, MeterConfig, MeterId};
+
+// Latest measurements, published by the scheduler for status servers.
+#[derive(Default)]
+pub struct Status {
+    measurements: ArcSwapOption<Measurements>,
+    names: ArcSwapOption<HashMap<MeterId, String>>,
+    descriptions: ArcSwapOption<Descriptions>,
+}
+
+impl Status {
+    pub fn update(&self, measurements: &Measurements) {
+        self.measurements
+            .store(Some(Arc::new(measurements.clone())));
+    }
+
+    // Name meters after the theme entries that use them.
+    pub fn set_names(&self, configs: &[MeterConfig]) {
+        let names = configs
+            .iter()
+            .map(|cfg| (cfg.id, cfg.name.clone()))
+            .collect();
+        self.names.store(Some(Arc::new(names)));
+    }
+
+    pub fn set_descriptions(&self, descriptions: Descriptions) {
+        self.descriptions.store(Some(Arc::new(descriptions)));
+    }
+
+    // Description and unit of each meter, by meter name.
+    pub fn named_descriptions(&self) -> HashMap<String, (String, String)> {
+        let Some(descriptions) = self.descriptions.load_full() else {
+            return HashMap::new();
+        };
+        let names = self.names.load_full().unwrap_or_default();
+        descriptions
+            .iter()
+            .map(|(id, desc)| (meter_name(&names, id), desc.clone()))
+            .collect()
+    }
+
+    // Current measurements by meter name. Meters without a name, such
+    // as extra values returned by a meter, use their id.
+    pub fn named_measurements(&self) -> BTreeMap<String, f32> {
+        let Some(measurements) = self.measurements.load_full() else {
+            return BTreeMap::new();
+        };
+        let names = self.names.load_full().unwrap_or_default();
+        measurements
+            .iter()
+            .map(|(id, &val)| (meter_name(&names, id), val))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.named_measurements()).unwrap_or_default()
+    }
+}
+
+fn meter_name(names: &HashMap<MeterId, String>, id: &MeterId) -> String {
+    names.get(id).cloned().unwrap_or_else(|| id.to_string())
+}
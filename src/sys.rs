@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::thread;
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+// Read one of the load average fields from /proc/loadavg.
+fn loadavg(index: usize) -> Res<f32> {
+    let s = fs::read_to_string("/proc/loadavg")?;
+    match s.split_whitespace().nth(index) {
+        Some(val) => Ok(val.parse::<f32>()?),
+        None => Err("invalid /proc/loadavg".into()),
+    }
+}
+
+// The upper end of the load and process count ranges is the number of CPUs.
+fn cpu_count() -> f32 {
+    thread::available_parallelism().map_or(1, |n| n.get()) as f32
+}
+
+// Load average over 1 minute
+
+#[derive(Debug)]
+pub struct SysLoad1 {
+    pub id: MeterId,
+}
+
+impl SysLoad1 {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for SysLoad1 {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        loadavg(0)
+    }
+
+    fn name(&self) -> &str {
+        "Load average over 1 minute"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, cpu_count())
+    }
+}
+
+// Load average over 5 minutes
+
+#[derive(Debug)]
+pub struct SysLoad5 {
+    pub id: MeterId,
+}
+
+impl SysLoad5 {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for SysLoad5 {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        loadavg(1)
+    }
+
+    fn name(&self) -> &str {
+        "Load average over 5 minutes"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, cpu_count())
+    }
+}
+
+// Load average over 15 minutes
+
+#[derive(Debug)]
+pub struct SysLoad15 {
+    pub id: MeterId,
+}
+
+impl SysLoad15 {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for SysLoad15 {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        loadavg(2)
+    }
+
+    fn name(&self) -> &str {
+        "Load average over 15 minutes"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, cpu_count())
+    }
+}
+
+// Uptime
+
+#[derive(Debug)]
+pub struct SysUptimeHours {
+    pub id: MeterId,
+}
+
+impl SysUptimeHours {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for SysUptimeHours {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let s = fs::read_to_string("/proc/uptime")?;
+        match s.split_whitespace().next() {
+            Some(val) => Ok(val.parse::<f32>()? / 3600.0),
+            None => Err("invalid /proc/uptime".into()),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Uptime"
+    }
+
+    fn unit(&self) -> &str {
+        "h"
+    }
+
+    // A week.
+    fn range(&self) -> (f32, f32) {
+        (0.0, 7.0 * 24.0)
+    }
+}
+
+// Number of running processes
+
+#[derive(Debug)]
+pub struct SysProcessCount {
+    pub id: MeterId,
+}
+
+impl SysProcessCount {
+    pub fn new(id: MeterId) -> Res<Self> {
+        Ok(Self { id })
+    }
+}
+
+impl Meter for SysProcessCount {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    fn measure(&mut self) -> Res<f32> {
+        let s = fs::read_to_string("/proc/stat")?;
+        parse_procs_running(&s)
+    }
+
+    fn name(&self) -> &str {
+        "Running processes"
+    }
+
+    fn range(&self) -> (f32, f32) {
+        (0.0, cpu_count())
+    }
+}
+
+fn parse_procs_running(stat: &str) -> Res<f32> {
+    for line in stat.lines() {
+        if let Some(val) = line.strip_prefix("procs_running ") {
+            return Ok(val.trim().parse::<f32>()?);
+        }
+    }
+    Err("procs_running not found in /proc/stat".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_procs_running() {
+        let stat = include_str!("../tests/fixtures/proc_stat");
+        assert_eq!(parse_procs_running(stat).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_parse_procs_running_missing() {
+        assert!(parse_procs_running("cpu  1 2 3 4\n").is_err());
+    }
+}
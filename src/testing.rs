@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::sync::{Arc, Mutex};
+
+use turing_screen::{Rgba, Screen};
+
+use crate::dryrun::rgb565_to_rgba;
+use crate::Res;
+
+// Screen operations recorded by MockScreen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScreenCall {
+    Init,
+    ScreenOn,
+    ScreenOff,
+    SetBrightness(i32),
+    DrawBitmap {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+}
+
+#[derive(Default)]
+struct State {
+    calls: Vec<ScreenCall>,
+    pixels: Vec<Rgba>,
+}
+
+// Screen that records all calls and keeps the drawn pixels in memory.
+// Clones share the same state, so a test can keep one to inspect what
+// the renderer did with the other.
+#[derive(Clone)]
+pub struct MockScreen {
+    state: Arc<Mutex<State>>,
+    width: usize,
+    height: usize,
+}
+
+impl MockScreen {
+    pub fn new(width: usize, height: usize) -> Self {
+        let state = State {
+            calls: Vec::new(),
+            pixels: vec![Rgba::default(); width * height],
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            width,
+            height,
+        }
+    }
+
+    pub fn calls(&self) -> Vec<ScreenCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgba {
+        self.state.lock().unwrap().pixels[y * self.width + x]
+    }
+
+    fn record(&self, call: ScreenCall) {
+        self.state.lock().unwrap().calls.push(call);
+    }
+}
+
+impl Screen for MockScreen {
+    fn init(&mut self) -> Res<()> {
+        self.record(ScreenCall::Init);
+        Ok(())
+    }
+
+    fn screen_on(&mut self) -> Res<()> {
+        self.record(ScreenCall::ScreenOn);
+        Ok(())
+    }
+
+    fn screen_off(&mut self) -> Res<()> {
+        self.record(ScreenCall::ScreenOff);
+        Ok(())
+    }
+
+    fn set_brightness(&mut self, level: i32) -> Res<()> {
+        self.record(ScreenCall::SetBrightness(level));
+        Ok(())
+    }
+
+    fn screen_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn draw_bitmap(
+        &mut self,
+        data: &[u8],
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Res<()> {
+        self.record(ScreenCall::DrawBitmap {
+            x,
+            y,
+            width,
+            height,
+        });
+        let mut state = self.state.lock().unwrap();
+        for (i, px) in data.chunks_exact(2).take(width * height).enumerate() {
+            let (col, row) = (x + i % width, y + i / width);
+            if col < self.width && row < self.height {
+                state.pixels[row * self.width + col] =
+                    rgb565_to_rgba(u16::from_le_bytes([px[0], px[1]]));
+            }
+        }
+        Ok(())
+    }
+}
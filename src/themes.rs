@@ -1,20 +1,26 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
-use bevy_reflect::{Reflect, Struct};
-use serde::Deserialize;
-use xxhash_rust::xxh3::xxh3_64;
+use bevy_reflect::{Reflect, ReflectRef, Struct};
+use serde::{de, Deserialize, Deserializer};
+use turing_screen::Rgba;
 
-use crate::meter::MeterConfig;
+use crate::meter::{source_meter_name, MeterConfig, MeterId};
+use crate::xdg::ResourceLocator;
 use crate::Res;
+use crate::{exec, file, mqtt, udp};
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 struct Display {
     display_orientation: String,
     display_rgb_led: String,
+    display_port: Option<String>,
+    display_serial_number: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -42,7 +48,25 @@ pub struct Text {
     pub y: u32,
     pub font: String,
     pub font_size: u32,
-    pub font_color: String,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub italic: bool,
+    #[reflect(ignore)]
+    #[serde(deserialize_with = "deserialize_color")]
+    pub font_color: Rgba,
+    #[reflect(ignore)]
+    #[serde(default, deserialize_with = "deserialize_thresholds")]
+    pub thresholds: Vec<(f32, Rgba)>,
+    pub format: Option<String>,
+    pub label: Option<String>,
+    pub unit_label: Option<String>,
+    pub unit_font_size: Option<u32>,
+    #[serde(default)]
+    pub rtl: bool,
+    #[serde(default)]
+    pub antialiased: bool,
+    pub blink_above: Option<f32>,
     pub background_color: Option<String>,
     pub background_image: Option<String>,
 }
@@ -50,25 +74,88 @@ pub struct Text {
 #[derive(Debug, Clone, Default, Deserialize, Reflect)]
 #[serde(rename_all = "UPPERCASE")]
 pub struct Graph {
-    show: bool,
-    x: u32,
-    y: u32,
-    width: u32,
-    height: u32,
-    min_value: String,
-    max_value: u32,
-    bar_color: String,
-    bar_outline: bool,
-    background_color: Option<String>,
-    background_image: Option<String>,
+    pub show: bool,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub min_value: String,
+    pub max_value: u32,
+    #[reflect(ignore)]
+    #[serde(deserialize_with = "deserialize_color")]
+    pub bar_color: Rgba,
+    #[reflect(ignore)]
+    #[serde(default, deserialize_with = "deserialize_thresholds")]
+    pub thresholds: Vec<(f32, Rgba)>,
+    #[reflect(ignore)]
+    #[serde(default, deserialize_with = "deserialize_gradient")]
+    pub gradient: Option<(Rgba, Rgba)>,
+    pub bar_outline: bool,
+    #[reflect(ignore)]
+    #[serde(default, deserialize_with = "deserialize_opt_color")]
+    pub background_color: Option<Rgba>,
+    pub background_image: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct Sparkline {
+    pub show: bool,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub history: u32,
+    #[reflect(ignore)]
+    #[serde(deserialize_with = "deserialize_color")]
+    pub line_color: Rgba,
+    #[reflect(ignore)]
+    #[serde(default, deserialize_with = "deserialize_opt_color")]
+    pub background_color: Option<Rgba>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct ArcGauge {
+    pub show: bool,
+    pub x: u32,
+    pub y: u32,
+    pub radius: u32,
+    pub thickness: u32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    #[reflect(ignore)]
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Rgba,
+    #[reflect(ignore)]
+    #[serde(default, deserialize_with = "deserialize_opt_color")]
+    pub track_color: Option<Rgba>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct Image {
+    pub path: String,
+    pub x: u32,
+    pub y: u32,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Reflect)]
 #[serde(rename_all = "UPPERCASE")]
 pub struct DeviceMeter {
     interval: Option<u32>,
+    source: Option<String>,
+    smoothing: Option<f32>,
+    min: Option<f32>,
+    max: Option<f32>,
+    transform: Option<String>,
+    jitter: Option<f32>,
+    priority: Option<u8>,
     pub text: Option<Text>,
     pub graph: Option<Graph>,
+    pub sparkline: Option<Sparkline>,
+    pub arc_gauge: Option<ArcGauge>,
+    pub image: Option<Image>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Reflect)]
@@ -80,13 +167,98 @@ pub struct DeviceStats {
     pub temperature: Option<DeviceMeter>,
 }
 
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct CpuStats {
+    pub interval: Option<u32>,
+    pub percentage: Option<DeviceMeter>,
+    pub frequency: Option<DeviceMeter>,
+    pub freq_mhz: Option<DeviceMeter>,
+    pub temperature: Option<DeviceMeter>,
+    pub core_pct: Option<Vec<DeviceMeter>>,
+    pub core_temp: Option<Vec<DeviceMeter>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct MemStats {
+    pub interval: Option<u32>,
+    pub used_percent: Option<DeviceMeter>,
+    pub used_bytes: Option<DeviceMeter>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct SwapStats {
+    pub interval: Option<u32>,
+    pub used_percent: Option<DeviceMeter>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct DiskStats {
+    pub interval: Option<u32>,
+    pub read_mbps: Option<DeviceMeter>,
+    pub write_mbps: Option<DeviceMeter>,
+    pub used_percent: Option<DeviceMeter>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct NetStats {
+    pub interval: Option<u32>,
+    pub rx_mbps: Option<DeviceMeter>,
+    pub tx_mbps: Option<DeviceMeter>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct BatStats {
+    pub interval: Option<u32>,
+    pub charge_percent: Option<DeviceMeter>,
+    pub is_charging: Option<DeviceMeter>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct FanStats {
+    pub interval: Option<u32>,
+    pub rpm: Option<DeviceMeter>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Reflect)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct SysStats {
+    pub interval: Option<u32>,
+    pub load_1: Option<DeviceMeter>,
+    pub load_5: Option<DeviceMeter>,
+    pub load_15: Option<DeviceMeter>,
+    pub uptime_hours: Option<DeviceMeter>,
+    pub process_count: Option<DeviceMeter>,
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Reflect)]
 #[serde(rename_all = "UPPERCASE")]
 pub struct Stats {
     pub interval: Option<u32>,
-    pub cpu: Option<DeviceStats>,
+    pub cpu: Option<CpuStats>,
     pub gpu: Option<DeviceStats>,
-    //pub memory: Option<MemoryStat>,
+    pub mem: Option<MemStats>,
+    pub swap: Option<SwapStats>,
+    pub disk: Option<DiskStats>,
+    pub net: Option<NetStats>,
+    pub bat: Option<BatStats>,
+    pub fan: Option<FanStats>,
+    pub sys: Option<SysStats>,
+    // Meters given by their source, named after the source hash.
+    #[reflect(ignore)]
+    pub cmd: Option<Vec<DeviceMeter>>,
+    #[reflect(ignore)]
+    pub file: Option<Vec<DeviceMeter>>,
+    #[reflect(ignore)]
+    pub udp: Option<Vec<DeviceMeter>>,
+    #[reflect(ignore)]
+    pub mqtt: Option<Vec<DeviceMeter>>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -95,18 +267,368 @@ pub struct Theme {
     static_images: StaticImages,
     #[serde(alias = "STATS")]
     pub stats: Stats,
+    #[serde(default)]
+    static_text: BTreeMap<String, StaticText>,
+    #[serde(default, deserialize_with = "deserialize_rotation")]
+    rotation: u16,
+    #[serde(default)]
+    flip_h: bool,
+    #[serde(default)]
+    flip_v: bool,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+// Fixed text drawn over the background, not bound to a meter.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct StaticText {
+    pub text: String,
+    pub x: u32,
+    pub y: u32,
+    pub font: String,
+    pub font_size: u32,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub italic: bool,
+    #[serde(default)]
+    pub rtl: bool,
+    #[serde(default)]
+    pub antialiased: bool,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub font_color: Rgba,
+}
+
+impl StaticText {
+    pub fn font_file(&self) -> String {
+        styled_font_file(&self.font, self.bold, self.italic)
+    }
+
+    pub fn scale(&mut self, factor: u32) {
+        self.x *= factor;
+        self.y *= factor;
+        self.font_size *= factor;
+    }
+}
+
+impl Text {
+    // Font file with the requested style.
+    pub fn font_file(&self) -> String {
+        styled_font_file(&self.font, self.bold, self.italic)
+    }
+}
+
+impl DeviceMeter {
+    // Describe widgets that don't fit in a screen of the given size.
+    pub fn check_bounds(&self, width: u32, height: u32) -> Vec<String> {
+        let mut boxes = Vec::<(&str, i64, i64, i64, i64)>::new();
+        if let Some(t) = &self.text {
+            boxes.push(("text", t.x.into(), t.y.into(), 1, 1));
+        }
+        if let Some(g) = &self.graph {
+            boxes.push((
+                "graph",
+                g.x.into(),
+                g.y.into(),
+                g.width.into(),
+                g.height.into(),
+            ));
+        }
+        if let Some(s) = &self.sparkline {
+            boxes.push((
+                "sparkline",
+                s.x.into(),
+                s.y.into(),
+                s.width.into(),
+                s.height.into(),
+            ));
+        }
+        if let Some(a) = &self.arc_gauge {
+            let r = i64::from(a.radius);
+            let (x, y) = (i64::from(a.x) - r, i64::from(a.y) - r);
+            boxes.push(("arc gauge", x, y, 2 * r + 1, 2 * r + 1));
+        }
+        if let Some(i) = &self.image {
+            boxes.push(("image", i.x.into(), i.y.into(), 1, 1));
+        }
+
+        let (width, height) = (i64::from(width), i64::from(height));
+        boxes
+            .into_iter()
+            .filter(|&(_, x, y, w, h)| x < 0 || y < 0 || x + w > width || y + h > height)
+            .map(|(widget, x, y, w, h)| {
+                format!("{widget} at ({x}, {y}) size {w}x{h} doesn't fit in {width}x{height}")
+            })
+            .collect()
+    }
+
+    // Multiply widget positions and sizes by an integer factor.
+    pub fn scale(&mut self, factor: u32) {
+        if let Some(t) = &mut self.text {
+            t.x *= factor;
+            t.y *= factor;
+            t.font_size *= factor;
+            t.unit_font_size = t.unit_font_size.map(|size| size * factor);
+        }
+        if let Some(g) = &mut self.graph {
+            g.x *= factor;
+            g.y *= factor;
+            g.width *= factor;
+            g.height *= factor;
+        }
+        if let Some(s) = &mut self.sparkline {
+            s.x *= factor;
+            s.y *= factor;
+            s.width *= factor;
+            s.height *= factor;
+        }
+        if let Some(a) = &mut self.arc_gauge {
+            a.x *= factor;
+            a.y *= factor;
+            a.radius *= factor;
+            a.thickness *= factor;
+        }
+        if let Some(i) = &mut self.image {
+            i.x *= factor;
+            i.y *= factor;
+        }
+    }
 }
 
-pub fn load(name: &str) -> Res<Theme> {
-    let filepath = format!("res/themes/{}/theme.yaml", name);
-    let theme: Theme = load_yaml(&filepath)?;
+impl Theme {
+    // The directory containing the theme file and its resources.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn background_path(&self) -> PathBuf {
+        self.dir.join(&self.static_images.background.path)
+    }
+
+    // The serial port of the screen, if bound to a specific device.
+    pub fn port(&self) -> Option<&str> {
+        self.display.display_port.as_deref()
+    }
+
+    // The serial number of the screen, if bound to a specific device.
+    pub fn serial_number(&self) -> Option<&str> {
+        self.display.display_serial_number.as_deref()
+    }
+
+    // Static text in name order, drawn over the background.
+    pub fn static_text(&self) -> impl Iterator<Item = &StaticText> {
+        self.static_text.values()
+    }
+
+    // Clockwise rotation of the screen contents in degrees.
+    pub fn rotation(&self) -> u16 {
+        self.rotation
+    }
+
+    // Mirror the screen contents left to right.
+    pub fn flip_h(&self) -> bool {
+        self.flip_h
+    }
+
+    // Mirror the screen contents top to bottom.
+    pub fn flip_v(&self) -> bool {
+        self.flip_v
+    }
+
+    // Screen size in pixels for the display orientation.
+    pub fn screen_size(&self) -> (u32, u32) {
+        match self.display.display_orientation.as_str() {
+            "landscape" | "reverse_landscape" => (480, 320),
+            _ => (320, 480),
+        }
+    }
+}
+
+// Font file with the requested style, such as DejaVuSans-Bold.ttf for a
+// bold DejaVuSans.ttf.
+fn styled_font_file(font: &str, bold: bool, italic: bool) -> String {
+    let style = match (bold, italic) {
+        (false, false) => return font.to_string(),
+        (true, false) => "Bold",
+        (false, true) => "Italic",
+        (true, true) => "BoldItalic",
+    };
+    let path = Path::new(font);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file = match path.extension() {
+        Some(ext) => format!("{stem}-{style}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{style}"),
+    };
+    path.with_file_name(file).to_string_lossy().into_owned()
+}
+
+// Parse a color given as "r, g, b", "r, g, b, a" or "#rrggbb".
+fn parse_color(s: &str) -> Res<Rgba> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 {
+            return Err(format!("invalid color: {s}").into());
+        }
+        let val = u32::from_str_radix(hex, 16)?;
+        return Ok(Rgba::new(
+            (val >> 16) as u8,
+            (val >> 8) as u8,
+            val as u8,
+            0xff,
+        ));
+    }
+
+    let c = s
+        .split(',')
+        .map(|v| v.trim().parse::<u8>())
+        .collect::<Result<Vec<_>, _>>()?;
+    match c[..] {
+        [r, g, b] => Ok(Rgba::new(r, g, b, 0xff)),
+        [r, g, b, a] => Ok(Rgba::new(r, g, b, a)),
+        _ => Err(format!("invalid color: {s}").into()),
+    }
+}
+
+fn deserialize_color<'de, D>(deserializer: D) -> Result<Rgba, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_color(&s).map_err(de::Error::custom)
+}
+
+fn deserialize_opt_color<'de, D>(deserializer: D) -> Result<Option<Rgba>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) => parse_color(&s).map(Some).map_err(de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+// Thresholds are pairs of value and color, sorted by value.
+fn deserialize_thresholds<'de, D>(deserializer: D) -> Result<Vec<(f32, Rgba)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut res = Vec::new();
+    for (value, color) in Vec::<(f32, String)>::deserialize(deserializer)? {
+        res.push((value, parse_color(&color).map_err(de::Error::custom)?));
+    }
+    res.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(res)
+}
+
+// Gradients are given as the low and high colors.
+fn deserialize_gradient<'de, D>(deserializer: D) -> Result<Option<(Rgba, Rgba)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<(String, String)>::deserialize(deserializer)? {
+        Some((low, high)) => {
+            let low = parse_color(&low).map_err(de::Error::custom)?;
+            let high = parse_color(&high).map_err(de::Error::custom)?;
+            Ok(Some((low, high)))
+        }
+        None => Ok(None),
+    }
+}
+
+// Theme file names, in order of preference.
+const THEME_FILES: [&str; 4] = ["theme.yaml", "theme.yml", "theme.toml", "theme.json"];
+
+pub const THEMES_DIR: &str = "res/themes";
+
+// Accept only quarter turns as the screen rotation.
+fn deserialize_rotation<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match u16::deserialize(deserializer)? {
+        degrees @ (0 | 90 | 180 | 270) => Ok(degrees),
+        degrees => Err(de::Error::custom(format!(
+            "invalid rotation {degrees}, must be 0, 90, 180 or 270"
+        ))),
+    }
+}
+
+// List the names of all themes found in the theme directories.
+pub fn list(themes: &ResourceLocator) -> Res<Vec<String>> {
+    let mut names = Vec::new();
+    for dir in themes.dirs() {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if THEME_FILES.iter().any(|f| path.join(f).exists()) {
+                if let Some(name) = path.file_name() {
+                    names.push(name.to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+pub fn load(themes: &ResourceLocator, name: &str) -> Res<Theme> {
+    let filepath = themes
+        .dirs()
+        .iter()
+        .flat_map(|dir| THEME_FILES.iter().map(move |f| dir.join(name).join(f)))
+        .find(|path| path.exists());
+
+    match filepath {
+        Some(path) => load_file(&path),
+        None => Err(format!("theme not found: {name}").into()),
+    }
+}
+
+// Load a theme file, detecting its format by extension.
+pub fn load_file(filepath: &Path) -> Res<Theme> {
+    let mut theme: Theme = match filepath.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => load_toml(filepath)?,
+        Some("json") => load_json(filepath)?,
+        _ => load_yaml(filepath)?,
+    };
+    theme.dir = filepath.parent().unwrap_or(Path::new(".")).to_path_buf();
+    check_limits(&theme)?;
 
     // TODO: check theme compatibility
 
     Ok(theme)
 }
 
-fn load_yaml<T>(filename: &str) -> Res<T>
+// Parse a YAML theme from memory. Relative paths in the theme are
+// resolved from the current directory.
+pub fn load_from_bytes(data: &[u8]) -> Res<Theme> {
+    let mut theme: Theme = serde_yaml::from_slice(data)?;
+    theme.dir = PathBuf::from(".");
+    check_limits(&theme)?;
+    Ok(theme)
+}
+
+// Reject meter limits that can't be used to clamp measurements. Missing
+// limits are infinite, so a meter is only bounded on the side given.
+fn check_limits(theme: &Theme) -> Res<()> {
+    for cfg in get_meter_list(theme) {
+        if cfg.min.is_nan() || cfg.max.is_nan() || cfg.min > cfg.max {
+            return Err(format!(
+                "invalid limits for {}: MIN {} and MAX {}",
+                cfg.name, cfg.min, cfg.max
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn load_yaml<T>(filename: &Path) -> Res<T>
 where
     T: serde::de::DeserializeOwned,
 {
@@ -116,6 +638,25 @@ where
     Ok(result)
 }
 
+fn load_toml<T>(filename: &Path) -> Res<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let data = std::fs::read_to_string(filename)?;
+    let result: T = toml::from_str(&data)?;
+    Ok(result)
+}
+
+fn load_json<T>(filename: &Path) -> Res<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let file = File::open(filename)?;
+    let reader = BufReader::new(file);
+    let result: T = serde_json::from_reader(reader)?;
+    Ok(result)
+}
+
 pub fn get_meter_list(theme: &Theme) -> Vec<MeterConfig> {
     // The default interval.
     let mut interval = 2;
@@ -130,32 +671,360 @@ pub fn get_meter_list(theme: &Theme) -> Vec<MeterConfig> {
     for (i, dev) in theme.stats.iter_fields().enumerate() {
         let mut dev_interval = interval;
         let dev_name = theme.stats.name_at(i).unwrap().to_uppercase();
-        if let Some(Some(dev_field)) = dev.downcast_ref::<Option<DeviceStats>>() {
+        if let Some(dev_field) = some_struct(dev) {
             // If device specifies a local interval, use it.
-            if let Some(val) = dev_field.interval {
-                dev_interval = val;
+            if let Some(Some(val)) = dev_field
+                .field("interval")
+                .and_then(|f| f.downcast_ref::<Option<u32>>())
+            {
+                dev_interval = *val;
             }
 
             // Iterate over device types.
             for (j, meter) in dev_field.iter_fields().enumerate() {
-                let mut meter_interval = dev_interval;
                 let meter_name = dev_field.name_at(j).unwrap().to_uppercase();
                 if let Some(Some(meter_field)) = meter.downcast_ref::<Option<DeviceMeter>>() {
-                    // If meter specifies a local interval, use it.
-                    if let Some(val) = meter_field.interval {
-                        meter_interval = val;
-                    }
-
                     // Add to list of existing meters.
-                    res.push(MeterConfig {
-                        id: xxh3_64(format!("{dev_name}:{meter_name}").as_bytes()),
-                        interval: meter_interval,
-                        layout: meter_field.clone(),
-                    });
+                    let name = format!("{dev_name}:{meter_name}");
+                    res.push(meter_config(&name, dev_interval, meter_field));
+                } else if let Some(Some(meter_list)) =
+                    meter.downcast_ref::<Option<Vec<DeviceMeter>>>()
+                {
+                    // Indexed meters, such as per-core stats.
+                    for (k, meter_field) in meter_list.iter().enumerate() {
+                        let name = format!("{dev_name}:{meter_name}_{k}");
+                        res.push(meter_config(&name, dev_interval, meter_field));
+                    }
                 }
             }
         }
     }
 
+    let sourced = [
+        (exec::PREFIX, &theme.stats.cmd),
+        (file::PREFIX, &theme.stats.file),
+        (udp::PREFIX, &theme.stats.udp),
+        (mqtt::PREFIX, &theme.stats.mqtt),
+    ];
+    for (prefix, meters) in sourced {
+        for meter in meters.iter().flatten() {
+            let name = source_meter_name(prefix, meter.source.as_deref().unwrap_or_default());
+            res.push(meter_config(&name, interval, meter));
+        }
+    }
+
     res
 }
+
+fn meter_config(name: &str, interval: u32, meter: &DeviceMeter) -> MeterConfig {
+    // If meter specifies a local interval, use it.
+    let interval = meter.interval.unwrap_or(interval);
+
+    MeterConfig {
+        id: MeterId::from_name(name),
+        name: name.to_string(),
+        interval,
+        source: meter.source.clone(),
+        smoothing: meter.smoothing.unwrap_or(0.0),
+        min: meter.min.unwrap_or(f32::NEG_INFINITY),
+        max: meter.max.unwrap_or(f32::INFINITY),
+        transform: meter.transform.clone(),
+        jitter: meter.jitter.unwrap_or(0.0),
+        priority: meter.priority.unwrap_or(0),
+        layout: meter.clone(),
+    }
+}
+
+// Get the struct inside a reflected Option field, if it's Some.
+fn some_struct(field: &dyn Reflect) -> Option<&dyn Struct> {
+    if let ReflectRef::Enum(e) = field.reflect_ref() {
+        if let Some(inner) = e.field_at(0) {
+            if let ReflectRef::Struct(s) = inner.reflect_ref() {
+                return Some(s);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_TOML: &str = include_str!("../res/themes/Sample_toml/theme.toml");
+
+    fn meter_ids(theme: &Theme) -> Vec<(MeterId, u32)> {
+        get_meter_list(theme)
+            .iter()
+            .map(|cfg| (cfg.id, cfg.interval))
+            .collect()
+    }
+
+    #[test]
+    fn test_toml_sample() {
+        let theme: Theme = toml::from_str(SAMPLE_TOML).unwrap();
+        assert_eq!(theme.static_images.background.path, "background.png");
+
+        let configs = get_meter_list(&theme);
+        assert_eq!(configs.len(), 3);
+
+        let cpu = &configs[0];
+        assert_eq!(cpu.id, MeterId::from_name("CPU:PERCENTAGE"));
+        assert_eq!(cpu.interval, 1);
+        let text = cpu.layout.text.as_ref().unwrap();
+        assert_eq!(text.font_color, Rgba::new(255, 255, 255, 255));
+        assert_eq!(text.format.as_deref(), Some("{:>3.0}%"));
+        let graph = cpu.layout.graph.as_ref().unwrap();
+        assert_eq!(graph.bar_color, Rgba::new(61, 184, 225, 255));
+
+        assert_eq!(configs[1].id, MeterId::from_name("CPU:TEMPERATURE"));
+        assert_eq!(configs[1].interval, 5);
+        assert_eq!(configs[2].id, MeterId::from_name("MEM:USED_PERCENT"));
+    }
+
+    #[test]
+    fn test_sample_resources() {
+        let themes = ResourceLocator::with_dir(Path::new(THEMES_DIR));
+        let fonts = ResourceLocator::with_dir(Path::new(crate::render::FONTS_DIR));
+        let theme = load(&themes, "Sample_toml").unwrap();
+        assert!(theme.background_path().exists());
+        for cfg in get_meter_list(&theme) {
+            if let Some(text) = &cfg.layout.text {
+                let font = text.font_file();
+                assert!(fonts.locate(&font).is_some(), "{font} not found");
+            }
+        }
+    }
+
+    #[test]
+    fn test_font_file() {
+        let mut text = Text {
+            font: "roboto/Roboto.ttf".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(text.font_file(), "roboto/Roboto.ttf");
+        text.bold = true;
+        assert_eq!(text.font_file(), "roboto/Roboto-Bold.ttf");
+        text.italic = true;
+        assert_eq!(text.font_file(), "roboto/Roboto-BoldItalic.ttf");
+        text.bold = false;
+        assert_eq!(text.font_file(), "roboto/Roboto-Italic.ttf");
+    }
+
+    #[test]
+    fn test_toml_matches_yaml() {
+        let yaml = r##"
+display:
+  DISPLAY_ORIENTATION: portrait
+  DISPLAY_RGB_LED: 61, 184, 225
+static_images:
+  BACKGROUND:
+    PATH: background.png
+    X: 0
+    Y: 0
+    WIDTH: 320
+    HEIGHT: 480
+STATS:
+  INTERVAL: 1
+  CPU:
+    INTERVAL: 1
+    PERCENTAGE:
+      TEXT:
+        SHOW: true
+        SHOW_UNIT: true
+        X: 40
+        Y: 60
+        FONT: dejavu/DejaVuSans-Bold.ttf
+        FONT_SIZE: 40
+        FONT_COLOR: 255, 255, 255
+        FORMAT: "{:>3.0}%"
+      GRAPH:
+        SHOW: true
+        X: 40
+        Y: 120
+        WIDTH: 240
+        HEIGHT: 20
+        MIN_VALUE: "0"
+        MAX_VALUE: 100
+        BAR_COLOR: 61, 184, 225
+        BAR_OUTLINE: true
+    TEMPERATURE:
+      INTERVAL: 5
+      TEXT:
+        SHOW: true
+        SHOW_UNIT: true
+        X: 40
+        Y: 180
+        FONT: dejavu/DejaVuSans-Bold.ttf
+        FONT_SIZE: 40
+        FONT_COLOR: "#ffffff"
+        FORMAT: "{:.1}°"
+  MEM:
+    USED_PERCENT:
+      GRAPH:
+        SHOW: true
+        X: 40
+        Y: 240
+        WIDTH: 240
+        HEIGHT: 20
+        MIN_VALUE: "0"
+        MAX_VALUE: 100
+        BAR_COLOR: 255, 128, 0
+        BAR_OUTLINE: false
+"##;
+        let from_yaml: Theme = serde_yaml::from_str(yaml).unwrap();
+        let from_toml: Theme = toml::from_str(SAMPLE_TOML).unwrap();
+        assert_eq!(meter_ids(&from_yaml), meter_ids(&from_toml));
+    }
+
+    #[test]
+    fn test_static_text() {
+        let yaml = r##"
+display:
+  DISPLAY_ORIENTATION: portrait
+  DISPLAY_RGB_LED: 0, 0, 0
+static_images:
+  BACKGROUND:
+    PATH: background.png
+    X: 0
+    Y: 0
+    WIDTH: 320
+    HEIGHT: 480
+static_text:
+  TITLE:
+    TEXT: CPU
+    X: 10
+    Y: 20
+    FONT: roboto/Roboto.ttf
+    FONT_SIZE: 30
+    FONT_COLOR: 255, 255, 255
+    BOLD: true
+STATS:
+  INTERVAL: 1
+"##;
+        let theme = load_from_bytes(yaml.as_bytes()).unwrap();
+        let texts: Vec<_> = theme.static_text().collect();
+        assert_eq!(texts.len(), 1);
+        assert_eq!(texts[0].text, "CPU");
+        assert_eq!(texts[0].font_file(), "roboto/Roboto-Bold.ttf");
+        assert!(get_meter_list(&theme).is_empty());
+    }
+
+    #[test]
+    fn test_sourced_meters() {
+        let yaml = r##"
+display:
+  DISPLAY_ORIENTATION: portrait
+  DISPLAY_RGB_LED: 0, 0, 0
+static_images:
+  BACKGROUND:
+    PATH: background.png
+    X: 0
+    Y: 0
+    WIDTH: 320
+    HEIGHT: 480
+STATS:
+  INTERVAL: 3
+  CMD:
+    - SOURCE: cat /tmp/value
+  FILE:
+    - SOURCE: /tmp/value
+      INTERVAL: 1
+"##;
+        let theme = load_from_bytes(yaml.as_bytes()).unwrap();
+        let configs = get_meter_list(&theme);
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].name, source_meter_name("CMD:", "cat /tmp/value"));
+        assert_eq!(configs[0].source.as_deref(), Some("cat /tmp/value"));
+        assert_eq!(configs[0].interval, 3);
+        assert_eq!(configs[1].name, source_meter_name("FILE:", "/tmp/value"));
+        assert_eq!(configs[1].interval, 1);
+    }
+
+    #[test]
+    fn test_invalid_limits() {
+        let load = |min: &str, max: &str| {
+            let yaml = format!(
+                r##"
+display:
+  DISPLAY_ORIENTATION: portrait
+  DISPLAY_RGB_LED: 0, 0, 0
+static_images:
+  BACKGROUND:
+    PATH: background.png
+    X: 0
+    Y: 0
+    WIDTH: 320
+    HEIGHT: 480
+STATS:
+  FILE:
+    - SOURCE: /tmp/value
+      MIN: {min}
+      MAX: {max}
+"##
+            );
+            load_from_bytes(yaml.as_bytes())
+        };
+
+        assert!(load("0", "100").is_ok());
+        assert!(load("10", "10").is_ok());
+        assert!(load("-.inf", "-10").is_ok());
+        assert!(load("10", ".inf").is_ok());
+        assert!(load("100", "0").is_err());
+        assert!(load(".nan", "100").is_err());
+        assert!(load("0", ".nan").is_err());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let yaml = r##"
+rotation: 270
+display:
+  DISPLAY_ORIENTATION: portrait
+  DISPLAY_RGB_LED: 0, 0, 0
+static_images:
+  BACKGROUND:
+    PATH: background.png
+    X: 0
+    Y: 0
+    WIDTH: 320
+    HEIGHT: 480
+STATS:
+  INTERVAL: 1
+"##;
+        let theme = load_from_bytes(yaml.as_bytes()).unwrap();
+        assert_eq!(theme.rotation(), 270);
+        let yaml = yaml.replace("rotation: 270", "rotation: 45");
+        assert!(load_from_bytes(yaml.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_scale() {
+        let mut meter = DeviceMeter {
+            text: Some(Text {
+                x: 10,
+                y: 20,
+                font_size: 16,
+                unit_font_size: Some(8),
+                ..Default::default()
+            }),
+            arc_gauge: Some(ArcGauge {
+                x: 50,
+                y: 60,
+                radius: 30,
+                thickness: 4,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        meter.scale(2);
+        let text = meter.text.unwrap();
+        assert_eq!((text.x, text.y, text.font_size), (20, 40, 32));
+        assert_eq!(text.unit_font_size, Some(16));
+        let gauge = meter.arc_gauge.unwrap();
+        assert_eq!(
+            (gauge.x, gauge.y, gauge.radius, gauge.thickness),
+            (100, 120, 60, 8)
+        );
+    }
+}
@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+
+use crate::meter::{Meter, MeterId};
+use crate::Res;
+
+// Name prefix of meters that receive values over UDP.
+pub const PREFIX: &str = "UDP:";
+
+// Latest value sent to a UDP port as text, such as "42.5".
+#[derive(Debug)]
+pub struct UdpMeter {
+    pub id: MeterId,
+    socket: UdpSocket,
+    last: Option<f32>,
+}
+
+impl UdpMeter {
+    // The source is a port number, or an address and port to listen on.
+    pub fn new(id: MeterId, source: Option<&str>) -> Res<Self> {
+        let addr = match source.map(str::trim) {
+            Some(port) if port.parse::<u16>().is_ok() => format!("0.0.0.0:{port}"),
+            Some(addr) => addr.to_string(),
+            None => return Err("UDP meter requires a source".into()),
+        };
+        let socket = UdpSocket::bind(&addr)?;
+        socket.set_nonblocking(true)?;
+        tracing::info!("listening for values on udp {addr}");
+        Ok(Self {
+            id,
+            socket,
+            last: None,
+        })
+    }
+}
+
+impl Meter for UdpMeter {
+    fn id(&self) -> MeterId {
+        self.id
+    }
+
+    // Read everything received since the last measurement, keeping the
+    // most recent valid value.
+    fn measure(&mut self) -> Res<f32> {
+        let mut buf = [0; 64];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) => match parse(&buf[..len]) {
+                    Some(val) => self.last = Some(val),
+                    None => tracing::debug!("invalid value from {from}"),
+                },
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        self.last.ok_or_else(|| "no value received".into())
+    }
+}
+
+fn parse(data: &[u8]) -> Option<f32> {
+    std::str::from_utf8(data).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_value() {
+        let mut meter = UdpMeter::new(MeterId(0), Some("127.0.0.1:0")).unwrap();
+        let addr = meter.socket.local_addr().unwrap();
+        assert!(meter.measure().is_err());
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for msg in ["1.5", "bogus", "42\n"] {
+            client.send_to(msg.as_bytes(), addr).unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(meter.measure().unwrap(), 42.0);
+
+        // The last value is kept until a new one arrives.
+        assert_eq!(meter.measure().unwrap(), 42.0);
+    }
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Res;
+
+// Consecutive failures before giving up.
+const MAX_FAILURES: u32 = 5;
+
+// A task running for this long before failing starts a new count.
+const HEALTHY_TIME: Duration = Duration::from_secs(60);
+
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+// Run a task again when it fails or panics, until it returns normally
+// or the quit flag is raised. After too many consecutive failures the
+// quit flag is raised to stop the program.
+pub fn supervise<F>(name: &str, quit: &AtomicBool, mut task: F)
+where
+    F: FnMut() -> Res<()>,
+{
+    let mut failures = 0;
+    loop {
+        let start = Instant::now();
+        let err = match panic::catch_unwind(AssertUnwindSafe(&mut task)) {
+            Ok(Ok(())) => return,
+            Ok(Err(err)) => err.to_string(),
+            Err(_) => "panicked".to_string(),
+        };
+        if quit.load(Ordering::Relaxed) {
+            return;
+        }
+        if start.elapsed() >= HEALTHY_TIME {
+            failures = 0;
+        }
+        failures += 1;
+        if failures == MAX_FAILURES {
+            tracing::error!("{name} failed {failures} times in a row, giving up: {err}");
+            quit.store(true, Ordering::Relaxed);
+            return;
+        }
+        tracing::error!("{name} error: {err}, restarting");
+        thread::sleep(RESTART_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart() {
+        let quit = AtomicBool::new(false);
+        let mut runs = 0;
+        supervise("test", &quit, || {
+            runs += 1;
+            match runs {
+                1 => Err("failed".into()),
+                2 => panic!("crashed"),
+                _ => Ok(()),
+            }
+        });
+        assert_eq!(runs, 3);
+        assert!(!quit.load(Ordering::Relaxed));
+    }
+}
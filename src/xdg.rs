@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(target_os = "linux")]
+use std::env;
+use std::path::{Path, PathBuf};
+
+const APP_NAME: &str = "turing-monitor";
+
+// Find resources in a list of directories, trying each one in order.
+#[derive(Debug, Clone)]
+pub struct ResourceLocator {
+    dirs: Vec<PathBuf>,
+}
+
+impl ResourceLocator {
+    // Search the XDG config and data directories for the resource
+    // subdirectory before the fallback directory.
+    pub fn new(subdir: &str, fallback: &Path) -> Self {
+        let mut dirs = Vec::new();
+
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(dir) = xdg_dir("XDG_CONFIG_HOME", ".config") {
+                dirs.push(dir.join(APP_NAME).join(subdir));
+            }
+            if let Some(dir) = xdg_dir("XDG_DATA_HOME", ".local/share") {
+                dirs.push(dir.join(APP_NAME).join(subdir));
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = subdir;
+
+        dirs.push(fallback.to_path_buf());
+
+        Self { dirs }
+    }
+
+    // Only search the given directory.
+    pub fn with_dir(dir: &Path) -> Self {
+        Self {
+            dirs: vec![dir.to_path_buf()],
+        }
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    // Return the path of the first match for the named resource.
+    pub fn locate(&self, name: impl AsRef<Path>) -> Option<PathBuf> {
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(name.as_ref()))
+            .find(|path| path.exists())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn xdg_dir(var: &str, default: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(default)),
+    }
+}
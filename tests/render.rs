@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use turing_monitor::mailbox;
+use turing_monitor::meter::{Measurements, MeterId, Ranges};
+use turing_monitor::render::{Message, Renderer};
+use turing_monitor::testing::{MockScreen, ScreenCall};
+use turing_monitor::themes;
+use turing_monitor::xdg::ResourceLocator;
+use turing_screen::Rgba;
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 480;
+
+const FONT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/fonts/dejavu");
+const FONT: &str = "DejaVuSans-Bold.ttf";
+
+const BLACK: Rgba = Rgba::new(0, 0, 0, 0xff);
+const RED: Rgba = Rgba::new(0xff, 0, 0, 0xff);
+const GREEN: Rgba = Rgba::new(0, 0xff, 0, 0xff);
+
+// Write a theme with a black background to a scratch directory.
+fn theme_dir(name: &str, stats: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("turing-monitor-{name}-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let background = vec![BLACK; WIDTH * HEIGHT];
+    lodepng::encode32_file(dir.join("background.png"), &background, WIDTH, HEIGHT).unwrap();
+
+    let theme = format!(
+        "\
+display:
+  DISPLAY_ORIENTATION: portrait
+  DISPLAY_RGB_LED: 0, 0, 0
+static_images:
+  BACKGROUND:
+    PATH: background.png
+    X: 0
+    Y: 0
+    WIDTH: {WIDTH}
+    HEIGHT: {HEIGHT}
+STATS:
+{stats}"
+    );
+    fs::write(dir.join("theme.yaml"), theme).unwrap();
+    dir
+}
+
+// Render a single set of measurements and return the screen.
+fn render(dir: &Path, fonts: ResourceLocator, measurements: Measurements) -> MockScreen {
+    render_on(MockScreen::new(WIDTH, HEIGHT), dir, fonts, measurements)
+}
+
+fn render_on(
+    scr: MockScreen,
+    dir: &Path,
+    fonts: ResourceLocator,
+    measurements: Measurements,
+) -> MockScreen {
+    let theme = themes::load_file(&dir.join("theme.yaml")).unwrap();
+    let configs = themes::get_meter_list(&theme);
+
+    let (tx, rx) = mailbox::channel();
+    let mut renderer = Renderer::new(
+        &theme,
+        configs,
+        Ranges::new(),
+        fonts,
+        Box::new(scr.clone()),
+        5,
+        1,
+    )
+    .unwrap();
+
+    // Queue everything before starting, the renderer exits on Quit.
+    tx.post(measurements).unwrap();
+    tx.send(Message::Quit).unwrap();
+    renderer.start(&rx).unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+    scr
+}
+
+#[test]
+fn test_screen_setup_and_shutdown() {
+    let dir = theme_dir("setup", "  INTERVAL: 1\n");
+    let scr = render(&dir, ResourceLocator::with_dir(&dir), Measurements::new());
+
+    let calls = scr.calls();
+    assert_eq!(
+        calls[..3],
+        [
+            ScreenCall::Init,
+            ScreenCall::ScreenOn,
+            ScreenCall::SetBrightness(5)
+        ]
+    );
+    assert_eq!(calls.last(), Some(&ScreenCall::ScreenOff));
+}
+
+#[test]
+fn test_render_graph() {
+    let stats = "\
+  MEM:
+    USED_PERCENT:
+      GRAPH:
+        SHOW: true
+        X: 40
+        Y: 240
+        WIDTH: 200
+        HEIGHT: 20
+        MIN_VALUE: \"0\"
+        MAX_VALUE: 100
+        BAR_COLOR: 255, 0, 0
+        BAR_OUTLINE: false
+";
+    let dir = theme_dir("graph", stats);
+    let measurements = Measurements::from([(MeterId::from_name("MEM:USED_PERCENT"), 25.0)]);
+    let scr = render(&dir, ResourceLocator::with_dir(&dir), measurements);
+
+    // A quarter of the bar is filled.
+    assert_eq!(scr.pixel(40, 240), RED);
+    assert_eq!(scr.pixel(89, 259), RED);
+    assert_eq!(scr.pixel(90, 250), BLACK);
+    assert_eq!(scr.pixel(239, 250), BLACK);
+    assert_eq!(scr.pixel(39, 250), BLACK);
+}
+
+#[test]
+fn test_render_graph_gradient() {
+    let stats = "\
+  MEM:
+    USED_PERCENT:
+      GRAPH:
+        SHOW: true
+        X: 40
+        Y: 240
+        WIDTH: 200
+        HEIGHT: 20
+        MIN_VALUE: \"0\"
+        MAX_VALUE: 100
+        BAR_COLOR: 255, 255, 255
+        GRADIENT: [\"0, 255, 0\", \"255, 0, 0\"]
+        BAR_OUTLINE: false
+";
+    let dir = theme_dir("gradient", stats);
+    let measurements = Measurements::from([(MeterId::from_name("MEM:USED_PERCENT"), 100.0)]);
+    let scr = render(&dir, ResourceLocator::with_dir(&dir), measurements);
+
+    // The bar goes from the low color on the left to the high color.
+    assert_eq!(scr.pixel(40, 250), GREEN);
+    assert_eq!(scr.pixel(239, 250), RED);
+    let middle = scr.pixel(140, 250);
+    assert!(middle.r > 0 && middle.g > 0 && middle.b == 0);
+}
+
+#[test]
+fn test_render_rotated() {
+    let stats = "\
+  MEM:
+    USED_PERCENT:
+      GRAPH:
+        SHOW: true
+        X: 0
+        Y: 0
+        WIDTH: 100
+        HEIGHT: 10
+        MIN_VALUE: \"0\"
+        MAX_VALUE: 100
+        BAR_COLOR: 255, 0, 0
+        BAR_OUTLINE: false
+";
+    let dir = theme_dir("rotated", stats);
+    let theme = fs::read_to_string(dir.join("theme.yaml")).unwrap();
+    fs::write(dir.join("theme.yaml"), format!("rotation: 90\n{theme}")).unwrap();
+    let measurements = Measurements::from([(MeterId::from_name("MEM:USED_PERCENT"), 100.0)]);
+    let scr = render_on(
+        MockScreen::new(HEIGHT, WIDTH),
+        &dir,
+        ResourceLocator::with_dir(&dir),
+        measurements,
+    );
+
+    // The top left corner of the theme is at the top right of the screen.
+    assert_eq!(scr.pixel(HEIGHT - 1, 0), RED);
+    assert_eq!(scr.pixel(HEIGHT - 10, 99), RED);
+    assert_eq!(scr.pixel(HEIGHT - 11, 0), BLACK);
+    assert_eq!(scr.pixel(HEIGHT - 1, 100), BLACK);
+    assert_eq!(scr.pixel(0, 0), BLACK);
+}
+
+#[test]
+fn test_render_text() {
+    let stats = format!(
+        "\
+  CPU:
+    PERCENTAGE:
+      TEXT:
+        SHOW: true
+        SHOW_UNIT: false
+        X: 40
+        Y: 60
+        FONT: {FONT}
+        FONT_SIZE: 60
+        FONT_COLOR: 255, 0, 0
+        FORMAT: \"{{:>3.0}}%\"
+"
+    );
+    let dir = theme_dir("text", &stats);
+    let measurements = Measurements::from([(MeterId::from_name("CPU:PERCENTAGE"), 88.0)]);
+    let scr = render(
+        &dir,
+        ResourceLocator::with_dir(Path::new(FONT_DIR)),
+        measurements,
+    );
+
+    // Text is drawn to the right of and below its position.
+    let drawn = |x0: usize, x1: usize, y0: usize, y1: usize| {
+        (y0..y1).any(|y| (x0..x1).any(|x| scr.pixel(x, y) != BLACK))
+    };
+    assert!(drawn(40, 200, 60, 120));
+    assert!(!drawn(0, WIDTH, 0, 50));
+    assert!(!drawn(0, WIDTH, 140, HEIGHT));
+}